@@ -0,0 +1,274 @@
+use std::fmt;
+use std::str::FromStr;
+
+use num::complex::Complex;
+
+use crate::COLORS;
+
+pub type Pixel = u32;
+
+const FNV_OFFSET: u64         = 0xcbf29ce484222325;
+const FNV_PRIME: u64          = 0x100000001b3;
+/// Roots are rounded to this many steps per unit before hashing, so float
+/// noise in numerically found roots does not change the palette.
+const HASH_PRECISION: f32     = 1e4;
+const GOLDEN_RATIO: f32       = 0.618034;
+const HASHED_SATURATION: f32  = 0.65;
+const HASHED_VALUE: f32       = 0.9;
+
+const CB_SAFE_COLORS: &[Pixel] =
+    &[ 0xe69f00,
+       0x56b4e9,
+       0x009e73,
+       0xf0e442,
+       0x0072b2,
+       0xd55e00,
+       0xcc79a7,
+       0x000000,
+    ];
+
+const VIRIDIS: &[Pixel] =
+    &[ 0x440154,
+       0x482475,
+       0x414487,
+       0x355f8d,
+       0x2a788e,
+       0x21918c,
+       0x22a884,
+       0x44bf70,
+       0x7ad151,
+       0xbddf26,
+       0xfde725,
+    ];
+
+pub fn to_rgb(p: &Pixel) -> (u8, u8, u8) {
+    let r: u8 = ((p >> 16) & 0xff) as u8;
+    let g: u8 = ((p >> 8) & 0xff) as u8;
+    let b: u8 = (p & 0xff) as u8;
+    (r, g, b)
+}
+
+pub fn from_rgb(r: u8, g: u8, b: u8) -> Pixel {
+    ((r as Pixel) << 16) | ((g as Pixel) << 8) | b as Pixel
+}
+
+/// Parses a hex color written as `RRGGBB`, `#RRGGBB` or `0xRRGGBB`.
+/// Pixels are 24-bit RGB, so an alpha byte (`RRGGBBAA`) is rejected rather
+/// than silently dropped by `to_rgb`.
+pub fn parse_color(s: &str) -> Result<Pixel, String> {
+    let hex = s.strip_prefix('#')
+        .or_else(|| s.strip_prefix("0x"))
+        .unwrap_or(s);
+    if hex.len() != 6 {
+        return Err(format!("Invalid color '{}': expected 6 hex digits RRGGBB, got {}{}",
+                           s, hex.len(), if hex.len() == 8 { " (alpha is not supported)" } else { "" }));
+    }
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(format!("Invalid color '{}': not a hex number", s));
+    }
+    Ok(Pixel::from_str_radix(hex, 16).unwrap())
+}
+
+/// A 24-bit color parsed from text or checked from an integer, for
+/// settings read from files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Color(pub Pixel);
+
+impl TryFrom<&str> for Color {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        parse_color(s).map(Color)
+    }
+}
+
+impl TryFrom<u32> for Color {
+    type Error = String;
+
+    fn try_from(n: u32) -> Result<Self, Self::Error> {
+        if n > 0xffffff {
+            return Err(format!("Invalid color {:#x}: more than 24 bits", n));
+        }
+        Ok(Color(n))
+    }
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Color::try_from(s)
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:06x}", self.0)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum PalettePreset {
+    Classic,
+    CbSafe,
+    Viridis,
+}
+
+impl FromStr for PalettePreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "classic" => Ok(PalettePreset::Classic),
+            "cb-safe" => Ok(PalettePreset::CbSafe),
+            "viridis" => Ok(PalettePreset::Viridis),
+            _ => Err("expected classic, cb-safe or viridis".to_string()),
+        }
+    }
+}
+
+/// Samples the viridis table at `t` in `[0, 1]`, interpolating between stops.
+pub fn viridis(t: f32) -> Pixel {
+    let pos = t.clamp(0.0, 1.0) * (VIRIDIS.len() - 1) as f32;
+    let i = (pos as usize).min(VIRIDIS.len() - 2);
+    let frac = pos - i as f32;
+    let (r0, g0, b0) = to_rgb(&VIRIDIS[i]);
+    let (r1, g1, b1) = to_rgb(&VIRIDIS[i + 1]);
+    let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * frac).round() as u8;
+    from_rgb(lerp(r0, r1), lerp(g0, g1), lerp(b0, b1))
+}
+
+/// Converts hue, saturation and value, each in `[0, 1]`, to a pixel.
+pub fn hsv(h: f32, s: f32, v: f32) -> Pixel {
+    let h = h.rem_euclid(1.0) * 6.0;
+    let c = v * s;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let q = |f: f32| ((f + v - c) * 255.0).round() as u8;
+    from_rgb(q(r), q(g), q(b))
+}
+
+fn hash_key(r: &Complex<f32>) -> (i64, i64) {
+    ((r.re * HASH_PRECISION).round() as i64, (r.im * HASH_PRECISION).round() as i64)
+}
+
+/// FNV-1a hash of the root set, independent of the roots' order.
+pub fn root_hash(roots: &[Complex<f32>]) -> u64 {
+    let mut keys: Vec<(i64, i64)> = roots.iter().map(hash_key).collect();
+    keys.sort_unstable();
+    let mut hash = FNV_OFFSET;
+    for (re, im) in keys {
+        for byte in re.to_le_bytes().into_iter().chain(im.to_le_bytes()) {
+            hash = (hash ^ byte as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
+    hash
+}
+
+/// `n` distinct colors whose hues start from a point picked by `seed` and
+/// step by the golden ratio, so neighbors stay far apart.
+pub fn generate_palette(seed: u64, n: usize) -> Vec<Pixel> {
+    let start = (seed >> 40) as f32 / (1u64 << 24) as f32;
+    (0..n)
+        .map(|i| hsv(start + i as f32 * GOLDEN_RATIO, HASHED_SATURATION, HASHED_VALUE))
+        .collect()
+}
+
+/// One color per root from `generate_palette` seeded with `root_hash`,
+/// handed out in sorted root order so each root keeps its color however
+/// the roots are listed.
+pub fn hashed_palette(roots: &[Complex<f32>]) -> Vec<Pixel> {
+    let colors = generate_palette(root_hash(roots), roots.len());
+    let keys: Vec<(i64, i64)> = roots.iter().map(hash_key).collect();
+    keys.iter().map(|k| colors[keys.iter().filter(|&other| other < k).count()]).collect()
+}
+
+/// Returns up to `n` colors of the preset; fixed presets may return fewer.
+pub fn palette(preset: PalettePreset, n: usize) -> Vec<Pixel> {
+    match preset {
+        PalettePreset::Classic => COLORS.iter().take(n).cloned().collect(),
+        PalettePreset::CbSafe  => CB_SAFE_COLORS.iter().take(n).cloned().collect(),
+        PalettePreset::Viridis => (0..n)
+            .map(|i| viridis(if n > 1 { i as f32 / (n - 1) as f32 } else { 0.5 }))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distinct(colors: &[Pixel]) -> usize {
+        let mut sorted = colors.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+        sorted.len()
+    }
+
+    #[test]
+    fn presets_return_distinct_colors() {
+        let presets = [(PalettePreset::Classic, 5), (PalettePreset::CbSafe, 8), (PalettePreset::Viridis, 7)];
+        for (preset, n) in presets {
+            let colors = palette(preset, n);
+            assert_eq!((colors.len(), distinct(&colors)), (n, n));
+        }
+        assert_eq!(palette(PalettePreset::CbSafe, 20).len(), CB_SAFE_COLORS.len());
+        assert_eq!(palette(PalettePreset::Viridis, 2), vec![VIRIDIS[0], VIRIDIS[VIRIDIS.len() - 1]]);
+    }
+
+    #[test]
+    fn hashed_palette_follows_the_roots() {
+        let roots = [Complex::new(-1.0, 0.0), Complex::new(0.0, 1.0), Complex::new(0.0, -1.0),
+                     Complex::new(0.5, 0.5)];
+        let colors = hashed_palette(&roots);
+        assert_eq!(colors, hashed_palette(&roots));
+        assert_eq!(distinct(&colors), roots.len());
+        let reordered = [roots[2], roots[0], roots[3], roots[1]];
+        assert_eq!(hashed_palette(&reordered), vec![colors[2], colors[0], colors[3], colors[1]]);
+        let noisy: Vec<Complex<f32>> = roots.iter().map(|r| r + Complex::new(1e-6, -1e-6)).collect();
+        assert_eq!(hashed_palette(&noisy), colors);
+        let other = [roots[0], roots[1], roots[2], Complex::new(0.5, -0.5)];
+        assert_ne!(root_hash(&other), root_hash(&roots));
+        assert_ne!(hashed_palette(&other), colors);
+    }
+
+    #[test]
+    fn color_accepts_hex_strings_and_24_bit_integers() {
+        for s in ["#1fa0cf", "0x1fa0cf", "1fa0cf", "#1FA0CF"] {
+            assert_eq!(Color::try_from(s), Ok(Color(0x1fa0cf)), "{}", s);
+            assert_eq!(s.parse::<Color>(), Ok(Color(0x1fa0cf)), "{}", s);
+        }
+        assert_eq!(Color::try_from(0x1fa0cf), Ok(Color(0x1fa0cf)));
+        assert_eq!(Color::try_from(0xffffff), Ok(Color(0xffffff)));
+        assert_eq!(Color(0x00a0cf).to_string(), "#00a0cf");
+        assert_eq!(Color(0x00a0cf).to_string().parse::<Color>(), Ok(Color(0x00a0cf)));
+    }
+
+    #[test]
+    fn color_rejects_malformed_input() {
+        for s in ["", "#", "#1fa0c", "blue", "#1fa0cg", "0x-1fa0c"] {
+            assert!(Color::try_from(s).is_err(), "{}", s);
+        }
+        assert!(Color::try_from("#1fa0cf80").unwrap_err().contains("alpha is not supported"));
+        let err = Color::try_from(0x1fa0cf80).unwrap_err();
+        assert!(err.contains("more than 24 bits"), "{}", err);
+    }
+
+    #[test]
+    fn colors_must_be_24_bit() {
+        for s in ["ff8000", "#ff8000", "0xff8000"] {
+            assert_eq!(parse_color(s), Ok(0xff8000));
+        }
+        let err = parse_color("#ff800080").unwrap_err();
+        assert!(err.contains("alpha is not supported"), "{}", err);
+        assert!(parse_color("#ff80").is_err());
+        assert!(parse_color("#gg8000").is_err());
+    }
+}
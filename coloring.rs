@@ -0,0 +1,246 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::color::{from_rgb, to_rgb, Pixel};
+use crate::render::{smooth_iterations, PixelResult, RenderConfig};
+
+/// How far `shade` darkens a basin color for the slowest pixels.
+const SHADE_DEPTH: f32 = 0.85;
+
+/// 4x4 ordered dither thresholds, in sixteenths.
+const BAYER: [[u8; 4]; 4] =
+    [ [ 0,  8,  2, 10],
+      [12,  4, 14,  6],
+      [ 3, 11,  1,  9],
+      [15,  7, 13,  5],
+    ];
+
+/// Global statistics of a classify pass, reduced once before coloring.
+pub struct Summary {
+    /// Converged pixels per iteration count, for the counts that occur.
+    pub histogram: BTreeMap<u32, usize>,
+    /// Converged pixels that needed at most the key's iterations.
+    pub cumulative: BTreeMap<u32, usize>,
+    pub converged: usize,
+    pub min_smooth: f32,
+    pub max_smooth: f32,
+}
+
+impl Summary {
+    /// Fraction of converged pixels that needed fewer than `iterations`.
+    pub fn cdf(&self, iterations: u32) -> f32 {
+        let below = self.cumulative.range(..iterations).next_back().map_or(0, |(_, &n)| n);
+        below as f32 / self.converged.max(1) as f32
+    }
+}
+
+/// Reduces a classify pass to its `Summary`. The histogram is sparse, so it
+/// takes any iteration count, such as those of results read back by
+/// `--recolor-from` that were classified with more iterations.
+pub fn summarize(config: &RenderConfig, results: &[PixelResult]) -> Summary {
+    let mut histogram = BTreeMap::new();
+    let mut converged = 0;
+    let (mut min_smooth, mut max_smooth) = (f32::INFINITY, f32::NEG_INFINITY);
+    for r in results.iter().filter(|r| r.converged) {
+        *histogram.entry(r.iterations).or_insert(0) += 1;
+        converged += 1;
+        let t = smooth_iterations(config, r);
+        min_smooth = min_smooth.min(t);
+        max_smooth = max_smooth.max(t);
+    }
+    let mut total = 0;
+    let cumulative = histogram.iter()
+        .map(|(&iterations, &n)| {
+            total += n;
+            (iterations, total)
+        })
+        .collect();
+    Summary{histogram, cumulative, converged, min_smooth, max_smooth}
+}
+
+/// Maps one classified pixel to its color, given the global `Summary`.
+/// `dither`, in `[-0.5, 0.5)`, is added to computed channels before they
+/// are rounded to 8 bits.
+pub trait ColorStrategy: Sync {
+    fn color(&self, config: &RenderConfig, summary: &Summary, r: &PixelResult, dither: f32) -> Pixel;
+}
+
+/// Ordered dither offset for pixel `(x, y)`, or 0 unless `config.dither`.
+/// The same pixel always gets the same offset.
+pub fn dither_offset(config: &RenderConfig, x: i32, y: i32) -> f32 {
+    if !config.dither {
+        return 0.0;
+    }
+    let m = BAYER[y.rem_euclid(4) as usize][x.rem_euclid(4) as usize];
+    (m as f32 + 0.5) / 16.0 - 0.5
+}
+
+/// Darkens `color` towards black as `t` goes from 0 to 1, adding `dither`
+/// to each channel before rounding.
+pub fn shade(color: Pixel, t: f32, dither: f32) -> Pixel {
+    let k = 1.0 - SHADE_DEPTH * t.clamp(0.0, 1.0);
+    let (r, g, b) = to_rgb(&color);
+    let q = |c: u8| (c as f32 * k + dither).round().clamp(0.0, 255.0) as u8;
+    from_rgb(q(r), q(g), q(b))
+}
+
+/// One flat color per basin.
+pub struct Basin;
+
+/// Basin colors shaded by the histogram-equalized iteration count, so the
+/// shading uses the full range however the counts are distributed.
+pub struct Histogram;
+
+/// Basin colors shaded by the smooth iteration count, stretched between
+/// the fastest and slowest converged pixels.
+pub struct Smooth;
+
+/// `config.tones.0` for basins of roots above the real axis, `.1` below it
+/// and an even mix of the two for real roots, however many roots there are.
+pub struct TwoTone;
+
+impl ColorStrategy for Basin {
+    fn color(&self, config: &RenderConfig, _: &Summary, r: &PixelResult, _: f32) -> Pixel {
+        config.colors[r.root_index]
+    }
+}
+
+impl ColorStrategy for Histogram {
+    fn color(&self, config: &RenderConfig, summary: &Summary, r: &PixelResult, dither: f32) -> Pixel {
+        let t = if r.converged { summary.cdf(r.iterations) } else { 1.0 };
+        shade(config.colors[r.root_index], t, dither)
+    }
+}
+
+impl ColorStrategy for Smooth {
+    fn color(&self, config: &RenderConfig, summary: &Summary, r: &PixelResult, dither: f32) -> Pixel {
+        let range = summary.max_smooth - summary.min_smooth;
+        let t = if !r.converged {
+            1.0
+        } else if range > 0.0 {
+            (smooth_iterations(config, r) - summary.min_smooth) / range
+        } else {
+            0.0
+        };
+        shade(config.colors[r.root_index], t, dither)
+    }
+}
+
+impl ColorStrategy for TwoTone {
+    fn color(&self, config: &RenderConfig, _: &Summary, r: &PixelResult, _: f32) -> Pixel {
+        let (above, below) = config.tones;
+        let im = config.roots[r.root_index].im;
+        if im >= config.epsilon {
+            above
+        } else if im <= -config.epsilon {
+            below
+        } else {
+            let (r0, g0, b0) = to_rgb(&above);
+            let (r1, g1, b1) = to_rgb(&below);
+            let mix = |a: u8, b: u8| (a as u16 + b as u16).div_ceil(2) as u8;
+            from_rgb(mix(r0, r1), mix(g0, g1), mix(b0, b1))
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Coloring {
+    Basin,
+    Histogram,
+    Smooth,
+    TwoTone,
+}
+
+impl Coloring {
+    pub fn strategy(&self) -> &dyn ColorStrategy {
+        match self {
+            Coloring::Basin     => &Basin,
+            Coloring::Histogram => &Histogram,
+            Coloring::Smooth    => &Smooth,
+            Coloring::TwoTone   => &TwoTone,
+        }
+    }
+}
+
+impl fmt::Display for Coloring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Coloring::Basin     => write!(f, "basin"),
+            Coloring::Histogram => write!(f, "histogram"),
+            Coloring::Smooth    => write!(f, "smooth"),
+            Coloring::TwoTone   => write!(f, "two-tone"),
+        }
+    }
+}
+
+impl FromStr for Coloring {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "basin"     => Ok(Coloring::Basin),
+            "histogram" => Ok(Coloring::Histogram),
+            "smooth"    => Ok(Coloring::Smooth),
+            "two-tone"  => Ok(Coloring::TwoTone),
+            _ => Err("expected basin, histogram, smooth or two-tone".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::fixtures::synthetic;
+    use crate::render::RenderConfigBuilder;
+
+    #[test]
+    fn summary_covers_iterations_beyond_steps() {
+        let config = RenderConfigBuilder::new().steps(20).build().unwrap();
+        let results = [synthetic(0, 36, true), synthetic(1, 3, true)];
+        let summary = summarize(&config, &results);
+        assert_eq!(summary.histogram, BTreeMap::from([(3, 1), (36, 1)]));
+        // Half the converged pixels are faster than the slow one, which is also
+        // the slowest by smooth iterations and sits on a real root.
+        let color = |coloring: Coloring| coloring.strategy().color(&config, &summary, &results[0], 0.0);
+        assert_eq!(summary.cdf(36), 0.5);
+        assert_eq!(color(Coloring::Basin), config.colors[0]);
+        assert_eq!(color(Coloring::Histogram), shade(config.colors[0], 0.5, 0.0));
+        assert_eq!(color(Coloring::Smooth), shade(config.colors[0], 1.0, 0.0));
+        assert_eq!(color(Coloring::TwoTone), 0x808080);
+    }
+
+    #[test]
+    fn summary_of_huge_iteration_counts_stays_small() {
+        let config = RenderConfigBuilder::new().steps(20).build().unwrap();
+        let results = [synthetic(0, 4_000_000_000, true), synthetic(1, 3, true)];
+        let summary = summarize(&config, &results);
+        assert_eq!(summary.histogram.len(), 2);
+        assert_eq!((summary.cdf(4_000_000_000), summary.cdf(u32::MAX)), (0.5, 1.0));
+        assert_eq!(Histogram.color(&config, &summary, &results[0], 0.0), shade(config.colors[0], 0.5, 0.0));
+    }
+
+    #[test]
+    fn summary_of_synthetic_results() {
+        let config = RenderConfigBuilder::new().steps(20).build().unwrap();
+        let results = [synthetic(0, 2, true), synthetic(1, 2, true), synthetic(2, 5, true),
+                       synthetic(0, 7, false)];
+        let summary = summarize(&config, &results);
+        assert_eq!(summary.converged, 3);
+        assert_eq!(summary.histogram, BTreeMap::from([(2, 2), (5, 1)]));
+        assert_eq!((summary.cdf(2), summary.cdf(3), summary.cdf(6), summary.cdf(20)),
+                   (0.0, 2.0 / 3.0, 1.0, 1.0));
+        assert_eq!((summary.min_smooth, summary.max_smooth), (0.1, 0.25));
+    }
+
+    #[test]
+    fn conjugate_roots_get_opposite_tones() {
+        let config = RenderConfigBuilder::new().two_tone(0xffffff, 0x000000).build().unwrap();
+        let summary = summarize(&config, &[]);
+        let tone = |root| TwoTone.color(&config, &summary, &synthetic(root, 3, true), 0.0);
+        assert_eq!(config.roots[1], config.roots[2].conj());
+        assert_eq!((tone(1), tone(2)), (0xffffff, 0x000000));
+        assert_eq!((tone(3), tone(4)), (0xffffff, 0x000000));
+        assert_eq!(tone(0), 0x808080);
+    }
+}
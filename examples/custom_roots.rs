@@ -0,0 +1,31 @@
+//! Renders the basins of z^4 - 1 with a hand-picked palette, without the CLI.
+//!
+//! Run with `cargo run --example custom_roots`; writes `custom_roots.ppm`.
+
+use std::fs::File;
+use std::io::{self, BufWriter};
+
+use newtf::output::write_ppm;
+use newtf::{render, RenderConfigBuilder};
+use num::complex::Complex;
+
+fn main() -> io::Result<()> {
+    let roots = vec![
+        Complex::new(1.0, 0.0),
+        Complex::new(0.0, 1.0),
+        Complex::new(-1.0, 0.0),
+        Complex::new(0.0, -1.0),
+    ];
+    let config = RenderConfigBuilder::new()
+        .width(640)
+        .height(480)
+        .scale(160.0)
+        .roots(roots)
+        .colors(vec![0xe63946, 0xf1c453, 0x2a9d8f, 0x264653])
+        .steps(40)
+        .build()
+        .unwrap_or_else(|e| panic!("invalid config: {}", e));
+
+    let canvas = render(&config).unwrap_or_else(|e| panic!("render failed: {}", e));
+    write_ppm(&mut BufWriter::new(File::create("custom_roots.ppm")?), &config.view, &canvas)
+}
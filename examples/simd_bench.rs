@@ -0,0 +1,48 @@
+//! Times `Polynom::at` against `Polynom::at_simd` over the rows of the
+//! default image and reports how far the two drift apart.
+//!
+//! Run with `cargo +nightly run --release --features simd --example simd_bench`.
+
+#![feature(portable_simd)]
+
+use std::simd::f32x8;
+use std::time::Instant;
+
+use newtf::poly::LANES;
+use newtf::{Polynom, Viewport};
+use num::complex::Complex;
+
+fn main() {
+    let pol = Polynom::from_roots();
+    let view = Viewport::default();
+    let rows: Vec<Vec<Complex<f32>>> = (0..view.height)
+        .map(|y| (0..view.width).map(|x| view.pixel_to_complex(x as f32, y as f32)).collect())
+        .collect();
+
+    let start = Instant::now();
+    let scalar: Vec<Vec<Complex<f32>>> = rows.iter()
+        .map(|row| row.iter().map(|&z| pol.at(z)).collect())
+        .collect();
+    let scalar_time = start.elapsed();
+
+    let start = Instant::now();
+    let lanes: Vec<Vec<Complex<f32>>> = rows.iter()
+        .map(|row| row.chunks(LANES).flat_map(|chunk| {
+            let lane = |f: fn(&Complex<f32>) -> f32| {
+                f32x8::from_array(std::array::from_fn(|i| chunk.get(i).map_or(0.0, f)))
+            };
+            let (re, im) = pol.at_simd(lane(|z| z.re), lane(|z| z.im));
+            let (re, im) = (re.to_array(), im.to_array());
+            (0..chunk.len()).map(move |i| Complex::new(re[i], im[i]))
+        }).collect())
+        .collect();
+    let simd_time = start.elapsed();
+
+    let mut max_error: f32 = 0.0;
+    for (a, b) in scalar.iter().flatten().zip(lanes.iter().flatten()) {
+        max_error = max_error.max((a - b).norm() / a.norm().max(1.0));
+    }
+    println!("Scalar: {:?}", scalar_time);
+    println!("SIMD:   {:?}", simd_time);
+    println!("Largest relative difference: {:e}", max_error);
+}
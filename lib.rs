@@ -0,0 +1,48 @@
+//! Newton's fractals: every point of the complex plane is colored by the
+//! root Newton's method converges to from it.
+//!
+//! `poly`, `color` and `render` form the numeric core and stay clear of
+//! files and the terminal; writing images and reports lives in `output`.
+
+#![cfg_attr(feature = "simd", feature(portable_simd))]
+
+use num::complex::Complex;
+
+pub mod color;
+pub mod coloring;
+pub mod output;
+pub mod poly;
+pub mod render;
+
+pub use color::{palette, parse_color, Color, PalettePreset, Pixel};
+pub use coloring::{ColorStrategy, Coloring, Summary};
+pub use poly::Polynom;
+pub use render::{classify, classify_pixels, color_pixels, render, render_field, render_rgba_bytes,
+                 render_with_progress, Fallback, PixelResult, RenderConfig, RenderConfigBuilder, RenderError,
+                 RenderStats, Viewport};
+
+pub const PIXELS_PER_UNIT: i32 = 100;
+pub const PX_WIDTH: i32        = 8 * PIXELS_PER_UNIT;
+pub const PX_HEIGHT: i32       = 6 * PIXELS_PER_UNIT;
+pub const STEPS: i32           = 20;
+pub const EPSILON: f32         = 1e-4;
+pub const ESCAPE_RADIUS: f32   = 1e10;
+pub const MAX_MEMORY: usize    = 2 << 30;
+
+const SQRT_3: f32 = 1.73205;
+
+pub const ROOTS: &[Complex<f32>] =
+    &[ Complex::new(-1.0, 0.0),
+       Complex::new((SQRT_3)/2.0, 1.0/2.0),
+       Complex::new((SQRT_3)/2.0, -1.0/2.0),
+       Complex::new(0.0, 1.0),
+       Complex::new(0.0, -1.0),
+    ];
+
+pub const COLORS: &[Pixel] =
+    &[ 0x4a0b58,
+       0x39538e,
+       0x1fa0cf,
+       0x56b861,
+       0x19858f,
+    ];
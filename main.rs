@@ -1,177 +1,622 @@
-use num::complex::Complex;
-use num::Zero;
 use std::fmt;
+use std::fs;
 use std::fs::File;
 use std::io;
-use std::io::{Write, BufWriter};
-use std::ops::MulAssign;
-use std::vec;
-
-const PIXELS_PER_UNIT: i32 = 100;
-const PX_WIDTH: i32        = 8 * PIXELS_PER_UNIT;
-const PX_HEIGHT: i32       = 6 * PIXELS_PER_UNIT;
-const STEPS: i32           = 20;
-
-const MAX_X: i32  = PX_WIDTH / 2;
-const MAX_Y: i32  = PX_HEIGHT / 2;
-const SQRT_3: f32 = 1.732050;
-
-const ROOTS: &[Complex<f32>] =
-    &[ Complex::new(-1.0, 0.0),
-       Complex::new((SQRT_3)/2.0, 1.0/2.0),
-       Complex::new((SQRT_3)/2.0, -1.0/2.0),
-       Complex::new(0.0, 1.0),
-       Complex::new(0.0, -1.0),
-    ];
-
-const COLORS: &[Pixel] =
-    &[ 0x4a0b58,
-       0x39538e,
-       0x1fa0cf,
-       0x56b861,
-       0x19858f,
-    ];
-
-type Pixel = u32;
-
-fn to_rgb(p: &Pixel) -> (u8, u8, u8) {
-    let r: u8 = ((p >> 16) & 0xff) as u8;
-    let g: u8 = ((p >> 8) & 0xff) as u8;
-    let b: u8 = ((p >> 0) & 0xff) as u8;
-    (r, g, b)
-}
+use std::io::{BufWriter, IsTerminal, Write};
+use std::process;
+use std::str::FromStr;
+use std::time::Instant;
 
-#[derive(Clone)]
-struct Polynom {
-    cs: Vec<Complex<f32>>,
-}
+use newtf::color::parse_color;
+use newtf::output::{read_data, read_pgm, read_poly, write_data, write_pfm, write_pgm, write_poly, write_ppm,
+                    write_stats_json, PixelData, PolyData};
+use newtf::render::{apply_mask, check_dynamics, classify_pixels, classify_pixels_with_progress, color_pixels,
+                    color_pixels_with_progress, draw_axes, iteration_heatmap, iteration_percentiles,
+                    mark_points, render_legend, render_stats, sample_grid, smooth_field};
+use newtf::{Pixel, PixelResult, Polynom, RenderConfig, RenderConfigBuilder, Viewport};
+use num::complex::Complex;
 
-impl MulAssign<Polynom> for Polynom {
-    fn mul_assign(&mut self, rhs: Polynom) {
-        let len = self.cs.len() - 1 + rhs.cs.len() - 1 + 1;
-        let mut res = Polynom{cs:vec![Complex::zero(); len]};
-        for i in 0..self.cs.len() {
-            for j in 0..rhs.cs.len() {
-                res.cs[i+j] += self.cs[i] * rhs.cs[j];
-            }
-        }
-        *self = res;
+const MAX_SLOW_PIXEL_LOGS: usize = 20;
+const CRITICAL_COLOR: Pixel      = 0xffffff;
+const PROGRESS_WIDTH: usize      = 40;
+const COMPARE_GRID: usize        = 64;
+const MASK_COLOR: Pixel          = 0x000000;
+
+/// Redraws the progress line on stderr at fraction `t` of the render,
+/// estimating the time left from the time spent since `start`.
+fn draw_progress(start: Instant, t: f32) {
+    let filled = ((t * PROGRESS_WIDTH as f32) as usize).min(PROGRESS_WIDTH);
+    let elapsed = start.elapsed().as_secs_f32();
+    let eta = if t > 0.0 { elapsed * (1.0 - t) / t } else { 0.0 };
+    eprint!("\r[{}{}] {:3.0}% {:.1}s elapsed, ETA {:.1}s ",
+            "#".repeat(filled), " ".repeat(PROGRESS_WIDTH - filled), t * 100.0, elapsed, eta);
+    if t >= 1.0 {
+        eprintln!();
     }
 }
 
-impl fmt::Display for Polynom {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for i in (0..self.cs.len()).rev() {
-            write!(f, "{}: ({}) ", i, self.cs[i])?;
+/// Reports pixels that did not converge within `limit` iterations to `s`,
+/// printing at most `MAX_SLOW_PIXEL_LOGS` of them.
+fn log_slow_pixels(s: &mut impl Write, pol: &Polynom, config: &RenderConfig, results: &[PixelResult],
+                   limit: u32) -> io::Result<()> {
+    let view = &config.view;
+    let mut slow = 0;
+    for (i, r) in results.iter().enumerate() {
+        if r.converged && r.iterations <= limit {
+            continue;
+        }
+        slow += 1;
+        if slow <= MAX_SLOW_PIXEL_LOGS {
+            let (x, y) = (i % view.width as usize, i / view.width as usize);
+            let at = view.pixel_to_complex(x as f32, y as f32);
+            if r.z.is_finite() {
+                writeln!(s, "Slow pixel ({}, {}) at {}: residual {}, nearest root {}",
+                         x, y, at, pol.at(r.z).norm(), config.roots[r.root_index])?;
+            } else {
+                // A non-finite orbit has no residual or nearest root to speak of.
+                writeln!(s, "Slow pixel ({}, {}) at {}: escaped", x, y, at)?;
+            }
         }
-        Ok(())
     }
+    if slow > MAX_SLOW_PIXEL_LOGS {
+        writeln!(s, "... and {} more slow pixels", slow - MAX_SLOW_PIXEL_LOGS)?;
+    }
+    Ok(())
 }
 
-impl Polynom {
+/// Largest and mean of a list of absolute errors.
+fn error_summary(errors: &[f64]) -> String {
+    let max = errors.iter().cloned().fold(0.0, f64::max);
+    let mean = errors.iter().sum::<f64>() / errors.len() as f64;
+    format!("max {:.3e}, mean {:.3e}", max, mean)
+}
 
-    fn at(&self, coord: Complex<f32>) -> Complex<f32> {
-        let mut res: Complex<f32> = Complex::zero();
-        for i in 0..self.cs.len() {
-            res += self.cs[i] * coord.powu(i as u32);
-        }
-        res
+/// Evaluates `pol` with `at` and `horner` on a `COMPARE_GRID` squared grid
+/// spanning `view`, and prints how far apart they are and how far each is
+/// from an f64 reference.
+fn compare_eval(pol: &Polynom, view: &Viewport) {
+    let (mut diff, mut naive, mut horner) = (Vec::new(), Vec::new(), Vec::new());
+    for (_, _, z) in sample_grid(view, COMPARE_GRID, COMPARE_GRID) {
+        let exact = pol.at_f64(Complex::new(z.re as f64, z.im as f64));
+        let (a, h) = (pol.at(z), pol.horner(z));
+        let err = |v: Complex<f32>| (Complex::new(v.re as f64, v.im as f64) - exact).norm();
+        diff.push((a - h).norm() as f64);
+        naive.push(err(a));
+        horner.push(err(h));
     }
+    println!("Naive vs Horner: {}", error_summary(&diff));
+    println!("Naive error: {}", error_summary(&naive));
+    println!("Horner error: {}", error_summary(&horner));
+}
 
-    fn from_roots() -> Polynom {
-        let mut pol = Polynom{
-            cs:vec![Complex::new(1.0, 0.0)]
-        };
-        for root in ROOTS.iter() {
-            pol *= Polynom{cs:vec![Complex::new(1.0, 0.0), -root]}
+/// Classifies and colors `config` again on a single thread and checks that
+/// `canvas`, the parallel render, matches it byte for byte, describing the
+/// first pixel that differs otherwise. Recolored `results` are colored again
+/// but not reclassified.
+fn verify_parallel(pol: &Polynom, der: &Polynom, config: &RenderConfig, results: &[PixelResult],
+                   recolored: bool, canvas: &[Pixel]) -> Result<(), String> {
+    let serial = RenderConfig{threads: 1, ..config.clone()};
+    let classified;
+    let serial_results = if recolored {
+        results
+    } else {
+        classified = classify_pixels(pol, der, &serial).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        });
+        &classified
+    };
+    let serial_canvas = color_pixels(pol, der, &serial, serial_results);
+    match canvas.iter().zip(&serial_canvas).position(|(a, b)| a != b) {
+        Some(i) => {
+            let width = config.view.width as usize;
+            Err(format!("Parallel render differs at pixel ({}, {}): #{:06x}, serially #{:06x}",
+                        i % width, i / width, canvas[i], serial_canvas[i]))
         }
-        pol
+        None => Ok(()),
     }
+}
 
-    fn derivative(&self) -> Polynom {
-        let mut res = self.clone();
-        for i in 0..res.cs.len()-1 {
-            res.cs[i] = Complex::new((i + 1) as f32, 0.0) * res.cs[i+1];
+/// Prints iteration count percentiles of converged pixels and a
+/// `--max-iterations` with some headroom over the 99th.
+fn report_percentiles(config: &RenderConfig, results: &[PixelResult]) {
+    match iteration_percentiles(results, &[50.0, 90.0, 99.0])[..] {
+        [p50, p90, p99] => {
+            println!("Iterations: p50 {}, p90 {}, p99 {}", p50, p90, p99);
+            let capped = results.iter().filter(|r| !r.converged && r.iterations >= config.steps).count();
+            if capped * 100 > results.len() {
+                println!("Suggested --max-iterations: more than {} ({} pixels hit the limit)",
+                         config.steps, capped);
+            } else {
+                println!("Suggested --max-iterations: {}", p99 + p99 / 4 + 1);
+            }
         }
-        res.cs.truncate(res.cs.len()-1);
-        res
+        _ => println!("Iterations: no pixel converged, try a larger --max-iterations"),
     }
 }
 
-fn between(x: f32, a: f32, b: f32) -> bool {
-    x >= a && x <= b
+struct Options {
+    config: RenderConfigBuilder,
+    timing: bool,
+    stats_json: Option<String>,
+    log_slow_pixels: Option<u32>,
+    monic: bool,
+    show_critical: bool,
+    field: Option<String>,
+    heatmap: Option<String>,
+    load_poly: Option<String>,
+    dump_poly: Option<String>,
+    dry_run: bool,
+    quiet: bool,
+    output: String,
+    batch: Option<String>,
+    compare_eval: bool,
+    verify_parallel: bool,
+    mask: bool,
+    mask_radius: Option<f32>,
+    percentiles: bool,
+    data: Option<String>,
+    recolor_from: Option<String>,
+    legend: Option<String>,
+    axes: bool,
+    grid: bool,
+    axes_color: Pixel,
+    seed_image: Option<String>,
+}
+
+fn next_value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String, String> {
+    args.next().ok_or_else(|| format!("Missing value for {}", flag))
+}
+
+fn next_parsed<T: FromStr>(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<T, String>
+where T::Err: fmt::Display {
+    let value = next_value(args, flag)?;
+    value.parse().map_err(|e| format!("Invalid value '{}' for {}: {}", value, flag, e))
 }
 
-fn get_color(pol: &Polynom, der: &Polynom, ic: Complex<f32>) -> Pixel {
-    let mut c = ic;
-    for _ in 0..STEPS {
-        let (yp, yd) = (pol.at(c), der.at(c));
-        if yd == Complex::zero() || c.is_nan() {
-            break;
-        }
-        c = c - yp / yd;
-        for i in 0..ROOTS.len() {
-            if ROOTS[i] == c {
-                return COLORS[i]
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut opts = Options{
+        config: RenderConfigBuilder::new(),
+        timing: false,
+        stats_json: None,
+        log_slow_pixels: None,
+        monic: false,
+        show_critical: false,
+        field: None,
+        heatmap: None,
+        load_poly: None,
+        dump_poly: None,
+        dry_run: false,
+        quiet: false,
+        output: "img.ppm".to_string(),
+        batch: None,
+        compare_eval: false,
+        verify_parallel: false,
+        mask: false,
+        mask_radius: None,
+        percentiles: false,
+        data: None,
+        recolor_from: None,
+        legend: None,
+        axes: false,
+        grid: false,
+        axes_color: 0xffffff,
+        seed_image: None,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--width"          => { opts.config.width(next_parsed(&mut args, &arg)?); }
+            "--height"         => { opts.config.height(next_parsed(&mut args, &arg)?); }
+            "--scale"          => { opts.config.scale(next_parsed(&mut args, &arg)?); }
+            "--max-memory"     => { opts.config.max_memory(next_parsed(&mut args, &arg)?); }
+            "--threads"        => { opts.config.threads(next_parsed(&mut args, &arg)?); }
+            "--palette-preset" => { opts.config.palette(next_parsed(&mut args, &arg)?); }
+            "--seed-from-hash" => { opts.config.hashed_palette(true); }
+            "--max-iterations" => { opts.config.steps(next_parsed(&mut args, &arg)?); }
+            "--epsilon"        => { opts.config.epsilon(next_parsed(&mut args, &arg)?); }
+            "--escape-radius"  => { opts.config.escape_radius(next_parsed(&mut args, &arg)?); }
+            "--patience"       => { opts.config.patience(next_parsed(&mut args, &arg)?); }
+            "--adaptive-aa"    => { opts.config.aa(next_parsed(&mut args, &arg)?); }
+            "--coloring"       => { opts.config.coloring(next_parsed(&mut args, &arg)?); }
+            "--two-tone"       => {
+                let above = parse_color(&next_value(&mut args, &arg)?)?;
+                opts.config.two_tone(above, parse_color(&next_value(&mut args, &arg)?)?);
             }
+            "--fallback"       => { opts.config.fallback(next_parsed(&mut args, &arg)?); }
+            "--cycle-color"    => { opts.config.cycle_color(parse_color(&next_value(&mut args, &arg)?)?); }
+            "--divergent-color" => {
+                opts.config.divergent_color(parse_color(&next_value(&mut args, &arg)?)?);
+            }
+            "--cycle-tolerance" => { opts.config.cycle_tolerance(next_parsed(&mut args, &arg)?); }
+            "--exploit-symmetry" => { opts.config.symmetry(true); }
+            "--dither"         => { opts.config.dither(true); }
+            "--only-root"      => { opts.config.only_root(next_parsed(&mut args, &arg)?); }
+            "--timing"         => opts.timing = true,
+            "--stats-json"     => opts.stats_json = Some(next_value(&mut args, &arg)?),
+            "--log-slow-pixels" => opts.log_slow_pixels = Some(next_parsed(&mut args, &arg)?),
+            "--monic"          => opts.monic = true,
+            "--show-critical"  => opts.show_critical = true,
+            "--field"          => opts.field = Some(next_value(&mut args, &arg)?),
+            "--heatmap"        => opts.heatmap = Some(next_value(&mut args, &arg)?),
+            "--load-poly"      => opts.load_poly = Some(next_value(&mut args, &arg)?),
+            "--dump-poly"      => opts.dump_poly = Some(next_value(&mut args, &arg)?),
+            "--dry-run"        => opts.dry_run = true,
+            "--quiet"          => opts.quiet = true,
+            "--output"         => opts.output = next_value(&mut args, &arg)?,
+            "--compare-eval"   => opts.compare_eval = true,
+            "--verify-parallel" => opts.verify_parallel = true,
+            "--mask"           => {
+                let shape = next_value(&mut args, &arg)?;
+                if shape != "circle" {
+                    return Err(format!("Invalid value '{}' for {}: expected circle", shape, arg));
+                }
+                opts.mask = true;
+            }
+            "--mask-radius"    => opts.mask_radius = Some(next_parsed(&mut args, &arg)?),
+            "--percentiles"    => opts.percentiles = true,
+            "--data"           => opts.data = Some(next_value(&mut args, &arg)?),
+            "--recolor-from"   => opts.recolor_from = Some(next_value(&mut args, &arg)?),
+            "--legend"         => opts.legend = Some(next_value(&mut args, &arg)?),
+            "--axes"           => opts.axes = true,
+            "--grid"           => opts.grid = true,
+            "--seed-image"     => opts.seed_image = Some(next_value(&mut args, &arg)?),
+            "--axes-color"     => opts.axes_color = parse_color(&next_value(&mut args, &arg)?)?,
+            "--batch"          => opts.batch = Some(next_value(&mut args, &arg)?),
+            _ => return Err(format!("Unknown argument '{}'", arg)),
         }
     }
+    Ok(opts)
+}
 
-    let dists: Vec<f32> =
-            ROOTS.iter()
-            .map(|r| {(c - r).norm()})
-            .collect();
-    let mut index = 0;
-    let mut min = dists.first().unwrap();
-    for i in 1..dists.len() {
-        if &dists[i] < min {
-            min = &dists[i];
-            index = i;
-        }
+fn main() -> io::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let opts = parse_args(args.iter().cloned()).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+    match &opts.batch {
+        Some(path) => run_batch(path, &args),
+        None => run(opts),
     }
-    COLORS[index]
 }
 
-fn write_ppm(s: &mut impl Write, canv: &Vec<Pixel>) -> io::Result<()> {
-    write!(s, "P6\n")?;
-    write!(s, "{} {}\n", PX_WIDTH, PX_HEIGHT)?;
-    write!(s, "255\n")?;
-    for y in 0..PX_HEIGHT {
-        for x in 0..PX_WIDTH {
-            let (r, g, b) = to_rgb(&canv[(y * PX_WIDTH + x) as usize]);
-            s.write(&vec![r, g, b])?;
+/// Renders the jobs listed in the manifest at `path`, one after another.
+/// Each line holds the options of one job, which are added to the ones
+/// given on the command line; blank lines and `#` comments are skipped.
+/// The whole manifest is parsed before the first job starts.
+fn run_batch(path: &str, args: &[String]) -> io::Result<()> {
+    let mut shared = Vec::new();
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        if arg == "--batch" {
+            it.next();
+        } else {
+            shared.push(arg.clone());
         }
     }
+    let manifest = fs::read_to_string(path)?;
+    let mut jobs = Vec::new();
+    for (n, line) in manifest.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let job_args = shared.iter().cloned().chain(line.split_whitespace().map(String::from));
+        let opts = parse_args(job_args).unwrap_or_else(|e| {
+            eprintln!("{}:{}: {}", path, n + 1, e);
+            process::exit(2);
+        });
+        if opts.batch.is_some() {
+            eprintln!("{}:{}: --batch cannot be nested", path, n + 1);
+            process::exit(2);
+        }
+        jobs.push((line, opts));
+    }
+    let start = Instant::now();
+    let count = jobs.len();
+    for (i, (line, opts)) in jobs.into_iter().enumerate() {
+        println!("Job {}: {}", i + 1, line);
+        let job_start = Instant::now();
+        run(opts)?;
+        println!("Job {} done in {:.3?}", i + 1, job_start.elapsed());
+    }
+    println!("Batch of {} jobs done in {:.3?}", count, start.elapsed());
     Ok(())
 }
 
-fn main() -> io::Result<()> {
-    assert!(ROOTS.len() != 0, "No roots specified");
-    assert!(ROOTS.len() <= COLORS.len(), "Not enough colors to mark all roots");
-    for root in ROOTS.iter() {
-        let mx = MAX_X as f32 / PIXELS_PER_UNIT as f32;
-        let my = MAX_Y as f32 / PIXELS_PER_UNIT as f32;
-        assert!(between(root.re, -mx, mx),
-                "Root {} is out of image bounds", root);
-        assert!(between(root.im, -my, my),
-                "Root {} is out of image bounds", root);
-    }
-    let pol = Polynom::from_roots();
+/// Renders the image described by `opts`, with its side outputs.
+fn run(mut opts: Options) -> io::Result<()> {
+    let recolor = match &opts.recolor_from {
+        Some(path) => {
+            let data = read_data(&fs::read_to_string(path)?).unwrap_or_else(|e| {
+                eprintln!("Invalid data in {}: {}", path, e);
+                process::exit(1);
+            });
+            opts.config.width(data.width).height(data.height).scale(data.scale);
+            Some(data)
+        }
+        None => None,
+    };
+    if let Some(path) = &opts.seed_image {
+        let (width, height, values) = read_pgm(&fs::read(path)?).unwrap_or_else(|e| {
+            eprintln!("Invalid seed image {}: {}", path, e);
+            process::exit(1);
+        });
+        opts.config.seed_image(width, height, values);
+    }
+    let mut pol = match &opts.load_poly {
+        Some(path) => {
+            let PolyData{pol, roots} = read_poly(&fs::read_to_string(path)?).unwrap_or_else(|e| {
+                eprintln!("Invalid polynomial in {}: {}", path, e);
+                process::exit(1);
+            });
+            check_dynamics(&pol.derivative()).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                process::exit(1);
+            });
+            opts.config.roots(roots.unwrap_or_else(|| pol.find_roots()));
+            pol
+        }
+        None => Polynom::from_roots(),
+    };
+    let config = opts.config.build().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let view = config.view;
+    if let Some(data) = &recolor {
+        if data.roots != config.roots.len() {
+            eprintln!("Data was classified against {} roots, but there are {}", data.roots, config.roots.len());
+            process::exit(1);
+        }
+    }
+    if opts.monic {
+        pol = pol.to_monic();
+    }
     let der = pol.derivative();
     println!("Pol: {}", pol);
     println!("Der: {}", der);
+    if opts.dry_run {
+        println!("{}", config);
+        return Ok(());
+    }
+    if opts.compare_eval {
+        compare_eval(&pol, &view);
+        return Ok(());
+    }
+    if let Some(path) = &opts.dump_poly {
+        write_poly(&mut BufWriter::new(File::create(path)?), &pol, &config.roots)?;
+    }
+
+    let show_progress = !opts.quiet && io::stderr().is_terminal();
+    let start = Instant::now();
+    let progress = |t: f32| if show_progress { draw_progress(start, t) };
+    let recolored = recolor.is_some();
+    let (results, classified, classify_evals) = match recolor {
+        Some(PixelData{results, ..}) => (results, 0.0, 0),
+        None => {
+            let (results, evals) = classify_pixels_with_progress(&pol, &der, &config, |t| progress(t / 2.0))
+                .unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                });
+            (results, 0.5, evals)
+        }
+    };
+    if let Some(limit) = opts.log_slow_pixels {
+        if show_progress {
+            eprintln!();
+        }
+        log_slow_pixels(&mut io::stderr(), &pol, &config, &results, limit)?;
+    }
+    let colored = |t: f32| progress(classified + t * (1.0 - classified));
+    let (mut canvas, color_evals) = color_pixels_with_progress(&pol, &der, &config, &results, colored);
+    let elapsed = start.elapsed();
+    if opts.timing {
+        let evals = classify_evals + color_evals;
+        println!("Render: {:.3?}", elapsed);
+        println!("Evaluations: {} ({:.2} per pixel)",
+                 evals, evals as f64 / canvas.len() as f64);
+    }
+    if opts.verify_parallel {
+        match verify_parallel(&pol, &der, &config, &results, recolored, &canvas) {
+            Ok(()) => println!("Parallel render matches the serial one"),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+    }
+    if opts.percentiles {
+        report_percentiles(&config, &results);
+    }
+    if opts.mask {
+        let (cx, cy) = (view.max_x() as f32, view.max_y() as f32);
+        let radius = opts.mask_radius.unwrap_or(view.width.min(view.height) as f32 / 2.0);
+        apply_mask(&mut canvas, &view, |x, y| {
+            (x as f32 - cx).hypot(y as f32 - cy) <= radius
+        }, MASK_COLOR);
+    }
+    if opts.axes || opts.grid {
+        draw_axes(&mut canvas, &view, opts.axes_color, opts.grid);
+    }
+    if opts.show_critical {
+        let critical = der.derivative().find_roots();
+        for p in critical.iter() {
+            println!("Critical point: {}", p);
+        }
+        mark_points(&mut canvas, &view, &critical, CRITICAL_COLOR);
+    }
 
-    let mut canvas = vec![0 as Pixel; (PX_WIDTH * PX_HEIGHT) as usize];
-    for y in 0..PX_HEIGHT {
-        for x in 0..PX_WIDTH {
-            let cx = (x - MAX_X) as f32 / PIXELS_PER_UNIT as f32;
-            let cy = (y - MAX_Y) as f32 / PIXELS_PER_UNIT as f32;
-            let col = get_color(&pol, &der, Complex::new(cx as f32, cy as f32));
-            canvas[(y * PX_WIDTH + x) as usize] = col
+    if let Some(path) = &opts.field {
+        write_pfm(&mut BufWriter::new(File::create(path)?), &view, &smooth_field(&config, &results))?;
+    }
+    if let Some(path) = &opts.heatmap {
+        write_pgm(&mut BufWriter::new(File::create(path)?), &view, &iteration_heatmap(&results))?;
+    }
+    if let Some(path) = &opts.data {
+        write_data(&mut BufWriter::new(File::create(path)?), &view, config.roots.len(), &results)?;
+    }
+    if let Some(path) = &opts.legend {
+        for (root, color) in config.roots.iter().zip(config.colors.iter()) {
+            println!("Legend: #{:06x} {}", color, root);
         }
+        let (legend_view, legend) = render_legend(&config);
+        write_ppm(&mut BufWriter::new(File::create(path)?), &legend_view, &legend)?;
+    }
+    if let Some(path) = &opts.stats_json {
+        let stats = render_stats(&results, config.roots.len(), elapsed);
+        write_stats_json(&mut BufWriter::new(File::create(path)?), &stats, &config)?;
     }
 
-    let mut of = BufWriter::new(File::create("img.ppm")?);
-    write_ppm(&mut of, &canvas)?;
+    let mut of = BufWriter::new(File::create(&opts.output)?);
+    write_ppm(&mut of, &view, &canvas)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slow_pixels_are_logged() {
+        let pol = Polynom::from_roots();
+        let config = RenderConfigBuilder::new().width(80).height(60).scale(10.0).build().unwrap();
+        let root = newtf::classify(&pol, &pol.derivative(), &config, config.roots[0]);
+        assert!(root.converged);
+        let mut results = vec![root; 80 * 60];
+        results[config.view.index(3, 2)] = PixelResult{converged: false, iterations: config.steps, ..root};
+        let mut log = Vec::new();
+        log_slow_pixels(&mut log, &pol, &config, &results, config.steps).unwrap();
+        let log = String::from_utf8(log).unwrap();
+        assert_eq!(log.lines().count(), 1);
+        assert!(log.starts_with("Slow pixel (3, 2) at "), "{}", log);
+
+        results[config.view.index(3, 2)].z = Complex::new(f32::NAN, 0.0);
+        let mut escaped = Vec::new();
+        log_slow_pixels(&mut escaped, &pol, &config, &results, config.steps).unwrap();
+        let escaped = String::from_utf8(escaped).unwrap();
+        assert!(escaped.starts_with("Slow pixel (3, 2) at ") && escaped.ends_with(": escaped\n"), "{}", escaped);
+
+        let mut flood = Vec::new();
+        log_slow_pixels(&mut flood, &pol, &config, &results, 0).unwrap();
+        let flood = String::from_utf8(flood).unwrap();
+        assert_eq!(flood.lines().count(), MAX_SLOW_PIXEL_LOGS + 1);
+        assert!(flood.ends_with(&format!("... and {} more slow pixels\n", 80 * 60 - MAX_SLOW_PIXEL_LOGS)));
+    }
+
+    fn args(list: &[&str]) -> impl Iterator<Item = String> {
+        list.iter().map(|s| s.to_string()).collect::<Vec<_>>().into_iter()
+    }
+
+    #[test]
+    fn dry_run_validates_the_settings() {
+        let valid = ["--dry-run", "--width", "80", "--height", "60", "--scale", "10"];
+        let opts = parse_args(args(&valid)).unwrap();
+        assert!(opts.dry_run && opts.config.build().is_ok());
+        assert!(run(opts).is_ok());
+        let opts = parse_args(args(&["--dry-run", "--scale", "0"])).unwrap();
+        assert!(opts.config.build().is_err());
+        assert!(parse_args(args(&["--dry-run", "--scale", "big"])).is_err());
+        assert!(parse_args(args(&["--dry-run", "--no-such-flag"])).is_err());
+    }
+
+    #[test]
+    fn recolor_keeps_the_classified_scale() {
+        let dir = std::env::temp_dir().join(format!("newtf-recolor-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (data, direct, recolored) = (dir.join("d.csv"), dir.join("direct.ppm"), dir.join("recolored.ppm"));
+        let path = |p: &std::path::PathBuf| p.display().to_string();
+        let render = |list: &[&str]| run(parse_args(args(list)).unwrap()).unwrap();
+        render(&["--width", "80", "--height", "60", "--scale", "10", "--quiet", "--coloring", "histogram",
+                 "--data", &path(&data), "--output", &path(&direct)]);
+        render(&["--quiet", "--coloring", "histogram", "--recolor-from", &path(&data),
+                 "--output", &path(&recolored)]);
+        assert!(fs::read(&recolored).unwrap() == fs::read(&direct).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn loaded_polynomial_is_normalized_only_with_monic() {
+        let dir = std::env::temp_dir().join(format!("newtf-monic-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (poly, dump, image) = (dir.join("p.txt"), dir.join("dump.txt"), dir.join("img.ppm"));
+        let text = "[[-2.5, 0], [0, 0], [0, 0], [2.5, 0]]";
+        fs::write(&poly, text).unwrap();
+        let loaded = read_poly(text).unwrap().pol;
+        let real = |cs: &[f32]| cs.iter().map(|&c| Complex::new(c, 0.0)).collect::<Vec<_>>();
+        assert_eq!(loaded.cs, real(&[-2.5, 0.0, 0.0, 2.5]));
+        assert_eq!(text.parse::<Polynom>().unwrap(), loaded);
+
+        let path = |p: &std::path::PathBuf| p.display().to_string();
+        let dumped = |extra: &[&str]| {
+            let mut list = vec!["--width", "80", "--height", "60", "--scale", "10", "--quiet"];
+            let (poly, dump, image) = (path(&poly), path(&dump), path(&image));
+            list.extend(["--load-poly", &poly, "--dump-poly", &dump, "--output", &image]);
+            list.extend(extra);
+            run(parse_args(args(&list)).unwrap()).unwrap();
+            read_poly(&fs::read_to_string(&dump).unwrap()).unwrap().pol
+        };
+        assert_eq!(dumped(&[]), loaded);
+        assert_eq!(dumped(&["--monic"]), loaded.to_monic());
+        assert_eq!(loaded.to_monic().cs, real(&[-1.0, 0.0, 0.0, 1.0]));
+
+        let config = RenderConfigBuilder::new().width(80).height(60).scale(10.0).roots(loaded.find_roots())
+            .build().unwrap();
+        let basins = |pol: &Polynom| -> Vec<(usize, bool)> {
+            classify_pixels(pol, &pol.derivative(), &config).unwrap().iter()
+                .map(|r| (r.root_index, r.converged))
+                .collect()
+        };
+        assert!(basins(&loaded) == basins(&loaded.to_monic()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn verify_parallel_reports_the_first_differing_pixel() {
+        let list = ["--verify-parallel", "--width", "80", "--height", "60", "--scale", "10", "--threads", "4",
+                    "--adaptive-aa", "2", "--quiet", "--output"];
+        let image = std::env::temp_dir().join(format!("newtf-verify-{}.ppm", process::id()));
+        let opts = parse_args(args(&list).chain([image.display().to_string()])).unwrap();
+        assert!(opts.verify_parallel);
+        run(opts).unwrap();
+        fs::remove_file(&image).unwrap();
+
+        let pol = Polynom::from_roots();
+        let der = pol.derivative();
+        let config = RenderConfigBuilder::new().width(80).height(60).scale(10.0).threads(4).build().unwrap();
+        let results = classify_pixels(&pol, &der, &config).unwrap();
+        let mut canvas = color_pixels(&pol, &der, &config, &results);
+        assert_eq!(verify_parallel(&pol, &der, &config, &results, false, &canvas), Ok(()));
+        let serial = canvas.clone();
+        for (x, y) in [(70, 50), (5, 12)] {
+            canvas[config.view.index(x, y)] ^= 0x010101;
+        }
+        let expected = format!("Parallel render differs at pixel (5, 12): #{:06x}, serially #{:06x}",
+                               canvas[config.view.index(5, 12)], serial[config.view.index(5, 12)]);
+        assert_eq!(verify_parallel(&pol, &der, &config, &results, true, &canvas), Err(expected.clone()));
+        assert_eq!(verify_parallel(&pol, &der, &config, &results, false, &canvas), Err(expected));
+    }
+
+    #[test]
+    fn batch_renders_every_job() {
+        let dir = std::env::temp_dir().join(format!("newtf-batch-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (a, b, manifest) = (dir.join("a.ppm"), dir.join("b.ppm"), dir.join("jobs.txt"));
+        fs::write(&manifest, format!("# two jobs\n--output {}\n\n--output {} --coloring histogram\n",
+                                     a.display(), b.display())).unwrap();
+        let manifest = manifest.display().to_string();
+        let shared: Vec<String> = args(&["--width", "80", "--height", "60", "--scale", "10", "--quiet",
+                                         "--batch", &manifest]).collect();
+        run_batch(&manifest, &shared).unwrap();
+        for (path, coloring) in [(&a, "basin"), (&b, "histogram")] {
+            let mut builder = RenderConfigBuilder::new();
+            builder.width(80).height(60).scale(10.0).coloring(coloring.parse().unwrap());
+            let config = builder.build().unwrap();
+            let mut expected = Vec::new();
+            write_ppm(&mut expected, &config.view, &newtf::render(&config).unwrap()).unwrap();
+            assert!(fs::read(path).unwrap() == expected, "{}", path.display());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,352 @@
+use std::io;
+use std::io::Write;
+
+use num::complex::Complex;
+
+use crate::color::{to_rgb, Pixel};
+use crate::poly::{parse_pairs, Polynom};
+use crate::render::{PixelResult, RenderConfig, RenderStats, Viewport};
+
+const DATA_HEADER: &str = "root_index,iterations,converged,cycle,escaped,re,im";
+
+/// A classify pass read back by `read_data`.
+pub struct PixelData {
+    pub width: i32,
+    pub height: i32,
+    /// Pixels per unit, so the roots fall where they did when classified.
+    pub scale: f32,
+    /// Number of roots the pixels were classified against.
+    pub roots: usize,
+    pub results: Vec<PixelResult>,
+}
+
+/// A polynomial read back by `read_poly`.
+pub struct PolyData {
+    pub pol: Polynom,
+    /// The roots saved with it, in order, if the file has them.
+    pub roots: Option<Vec<Complex<f32>>>,
+}
+
+pub fn write_stats_json(s: &mut impl Write, stats: &RenderStats, config: &RenderConfig) -> io::Result<()> {
+    let list = |xs: &mut dyn Iterator<Item = String>| xs.collect::<Vec<_>>().join(", ");
+    writeln!(s, "{{")?;
+    writeln!(s, "  \"basin_fractions\": [{}],",
+             list(&mut stats.basin_fractions.iter().map(|f| f.to_string())))?;
+    writeln!(s, "  \"divergent_fraction\": {},", stats.divergent_fraction)?;
+    writeln!(s, "  \"unconverged_fraction\": {},", stats.unconverged_fraction)?;
+    writeln!(s, "  \"mean_iterations\": {},", stats.mean_iterations)?;
+    writeln!(s, "  \"median_iterations\": {},", stats.median_iterations)?;
+    writeln!(s, "  \"max_iterations\": {},", stats.max_iterations)?;
+    writeln!(s, "  \"elapsed_seconds\": {},", stats.elapsed.as_secs_f64())?;
+    writeln!(s, "  \"config\": {{")?;
+    writeln!(s, "    \"width\": {},", config.view.width)?;
+    writeln!(s, "    \"height\": {},", config.view.height)?;
+    writeln!(s, "    \"pixels_per_unit\": {},", config.view.scale)?;
+    writeln!(s, "    \"steps\": {},", config.steps)?;
+    writeln!(s, "    \"epsilon\": {},", config.epsilon)?;
+    writeln!(s, "    \"adaptive_aa\": {},", config.aa)?;
+    writeln!(s, "    \"colors\": [{}],",
+             list(&mut config.colors.iter().map(|c| format!("\"#{:06x}\"", c))))?;
+    writeln!(s, "    \"roots\": [{}]",
+             list(&mut config.roots.iter().map(|r| format!("[{}, {}]", r.re, r.im))))?;
+    writeln!(s, "  }}")?;
+    writeln!(s, "}}")
+}
+
+/// Writes a grayscale Portable Float Map; PFM stores rows bottom to top.
+pub fn write_pfm(s: &mut impl Write, view: &Viewport, field: &[f32]) -> io::Result<()> {
+    writeln!(s, "Pf")?;
+    writeln!(s, "{} {}", view.width, view.height)?;
+    writeln!(s, "-1.0")?;
+    for y in (0..view.height).rev() {
+        for x in 0..view.width {
+            s.write_all(&field[view.index(x, y)].to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the per-pixel results of a classify pass as CSV: a
+/// `width,height,scale,roots` line, then one row per pixel in row-major
+/// order. Floats are written so that `read_data` restores them exactly.
+pub fn write_data(s: &mut impl Write, view: &Viewport, roots: usize, results: &[PixelResult]) -> io::Result<()> {
+    writeln!(s, "width,height,scale,roots")?;
+    writeln!(s, "{},{},{},{}", view.width, view.height, view.scale, roots)?;
+    writeln!(s, "{}", DATA_HEADER)?;
+    for r in results {
+        writeln!(s, "{},{},{},{},{},{},{}", r.root_index, r.iterations, r.converged as u8, r.cycle as u8,
+                 r.escaped as u8, r.z.re, r.z.im)?;
+    }
+    Ok(())
+}
+
+/// Parses a file written by `write_data`.
+pub fn read_data(text: &str) -> Result<PixelData, String> {
+    let mut lines = text.lines().enumerate().map(|(i, l)| (i + 1, l));
+    let mut next = |what: &str| lines.next().ok_or_else(|| format!("missing {}", what));
+    let (_, names) = next("size header")?;
+    let (n, size) = next("size")?;
+    let size: Vec<&str> = size.split(',').collect();
+    let (width, height, scale, roots) = match (names, &size[..]) {
+        ("width,height,scale,roots", [w, h, s, r]) => {
+            let bad = || format!("line {}: invalid size", n);
+            (w.parse::<i32>().map_err(|_| bad())?, h.parse::<i32>().map_err(|_| bad())?,
+             s.parse::<f32>().map_err(|_| bad())?, r.parse::<usize>().map_err(|_| bad())?)
+        }
+        _ => return Err("expected a width,height,scale,roots header".to_string()),
+    };
+    if width <= 0 || height <= 0 {
+        return Err(format!("line {}: size {}x{} must be positive", n, width, height));
+    }
+    if !(scale > 0.0 && scale.is_finite()) {
+        return Err(format!("line {}: scale {} must be positive and finite", n, scale));
+    }
+    match next("pixel header")? {
+        (_, DATA_HEADER) => {}
+        (n, _) => return Err(format!("line {}: expected '{}'", n, DATA_HEADER)),
+    }
+    // The header is not trusted to size the buffer: rows are counted as they
+    // are read, so a file claiming a huge image fails instead of allocating it.
+    let pixels = width as usize * height as usize;
+    let mut results = Vec::new();
+    for (n, line) in lines {
+        if results.len() == pixels {
+            return Err(format!("line {}: more rows than the {}x{} image has pixels", n, width, height));
+        }
+        let bad = || format!("line {}: expected {}", n, DATA_HEADER);
+        let fields: Vec<&str> = line.split(',').collect();
+        let [index, iterations, converged, cycle, escaped, re, im] = fields[..] else {
+            return Err(bad());
+        };
+        let flag = |f: &str| match f { "0" => Ok(false), "1" => Ok(true), _ => Err(bad()) };
+        let root_index: usize = index.parse().map_err(|_| bad())?;
+        if root_index >= roots {
+            return Err(format!("line {}: root index {} out of range", n, root_index));
+        }
+        results.push(PixelResult{
+            root_index,
+            iterations: iterations.parse().map_err(|_| bad())?,
+            converged: flag(converged)?,
+            cycle: flag(cycle)?,
+            escaped: flag(escaped)?,
+            z: Complex::new(re.parse().map_err(|_| bad())?, im.parse().map_err(|_| bad())?),
+        });
+    }
+    if results.len() != pixels {
+        return Err(format!("expected {} pixels for {}x{}, found {}", pixels, width, height, results.len()));
+    }
+    Ok(PixelData{width, height, scale, roots, results})
+}
+
+/// Reads a binary (P5) PGM, returning its size and its pixels in row-major
+/// order, scaled to 0..=255.
+pub fn read_pgm(bytes: &[u8]) -> Result<(i32, i32, Vec<u8>), String> {
+    let mut pos = 0;
+    let mut fields = Vec::new();
+    while fields.len() < 4 {
+        while pos < bytes.len() && (bytes[pos].is_ascii_whitespace() || bytes[pos] == b'#') {
+            if bytes[pos] == b'#' {
+                while pos < bytes.len() && bytes[pos] != b'\n' {
+                    pos += 1;
+                }
+            } else {
+                pos += 1;
+            }
+        }
+        let start = pos;
+        while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if start == pos {
+            return Err("truncated header".to_string());
+        }
+        fields.push(String::from_utf8_lossy(&bytes[start..pos]).into_owned());
+    }
+    if fields[0] != "P5" {
+        return Err("expected a binary PGM (P5)".to_string());
+    }
+    let number = |s: &str| s.parse::<i32>().ok().filter(|&n| n > 0).ok_or(format!("invalid header value '{}'", s));
+    let (width, height, max) = (number(&fields[1])?, number(&fields[2])?, number(&fields[3])?);
+    if max > 255 {
+        return Err(format!("16-bit PGMs (maxval {}) are not supported", max));
+    }
+    let pixels = bytes.get(pos + 1..).unwrap_or(&[]);
+    let n = width as usize * height as usize;
+    if pixels.len() < n {
+        return Err(format!("expected {} pixels for {}x{}, found {}", n, width, height, pixels.len()));
+    }
+    Ok((width, height, pixels[..n].iter().map(|&v| (v as u32 * 255 / max as u32) as u8).collect()))
+}
+
+/// Writes an 8-bit binary PGM, the format `read_pgm` reads.
+pub fn write_pgm(s: &mut impl Write, view: &Viewport, values: &[u8]) -> io::Result<()> {
+    writeln!(s, "P5")?;
+    writeln!(s, "{} {}", view.width, view.height)?;
+    writeln!(s, "255")?;
+    for y in 0..view.height {
+        let row = view.index(0, y);
+        s.write_all(&values[row..row + view.width as usize])?;
+    }
+    Ok(())
+}
+
+/// Writes `pol` in the form `Polynom::from_str` reads back exactly, then
+/// `roots` on a second line in the same form. Keeping the roots keeps their
+/// order, and with it which color marks which basin when `read_poly` loads
+/// the file again.
+pub fn write_poly(s: &mut impl Write, pol: &Polynom, roots: &[Complex<f32>]) -> io::Result<()> {
+    let pairs = |cs: &[Complex<f32>]| cs.iter().map(|c| format!("[{}, {}]", c.re, c.im)).collect::<Vec<_>>();
+    writeln!(s, "[{}]", pairs(&pol.cs).join(", "))?;
+    writeln!(s, "[{}]", pairs(roots).join(", "))
+}
+
+/// Parses a file written by `write_poly`. The roots line is optional.
+pub fn read_poly(text: &str) -> Result<PolyData, String> {
+    let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+    let pol: Polynom = lines.next().ok_or("missing coefficients")?.parse()?;
+    let roots = lines.next().map(parse_pairs).transpose()?;
+    if lines.next().is_some() {
+        return Err("expected coefficients and at most one line of roots".to_string());
+    }
+    if let Some(roots) = &roots {
+        if roots.len() != pol.degree() {
+            return Err(format!("{} roots for a polynomial of degree {}", roots.len(), pol.degree()));
+        }
+    }
+    Ok(PolyData{pol, roots})
+}
+
+pub fn write_ppm(s: &mut impl Write, view: &Viewport, canv: &[Pixel]) -> io::Result<()> {
+    writeln!(s, "P6")?;
+    writeln!(s, "{} {}", view.width, view.height)?;
+    writeln!(s, "255")?;
+    for y in 0..view.height {
+        for x in 0..view.width {
+            let (r, g, b) = to_rgb(&canv[view.index(x, y)]);
+            s.write_all(&[r, g, b])?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coloring::Coloring;
+    use crate::render::fixtures::small;
+    use crate::render::{classify_pixels, color_pixels, render, render_stats};
+    use std::time::Duration;
+
+    #[test]
+    fn recolor_round_trip_matches_direct_render() {
+        let pol = Polynom::from_roots();
+        let der = pol.derivative();
+        let config = small().build().unwrap();
+        let mut data = Vec::new();
+        let results = classify_pixels(&pol, &der, &config).unwrap();
+        write_data(&mut data, &config.view, config.roots.len(), &results).unwrap();
+        let read = read_data(&String::from_utf8(data).unwrap()).unwrap();
+        assert_eq!((read.width, read.height, read.scale, read.roots), (80, 60, 10.0, config.roots.len()));
+        for coloring in [Coloring::Basin, Coloring::Histogram, Coloring::Smooth] {
+            let config = small().coloring(coloring).build().unwrap();
+            assert!(color_pixels(&pol, &der, &config, &read.results) == render(&config).unwrap());
+        }
+    }
+
+    #[test]
+    fn recolor_with_fewer_steps_than_classified() {
+        let pol = Polynom::from_roots();
+        let der = pol.derivative();
+        let slow = small().steps(60).epsilon(1e-6).build().unwrap();
+        let results = classify_pixels(&pol, &der, &slow).unwrap();
+        assert!(results.iter().any(|r| r.iterations > 20));
+        let config = small().coloring(Coloring::Histogram).build().unwrap();
+        let canvas = color_pixels(&pol, &der, &config, &results);
+        let brightness = |p: &Pixel| {
+            let (r, g, b) = to_rgb(p);
+            r as u32 + g as u32 + b as u32
+        };
+        // Within a basin, pixels that took more iterations are shaded no lighter.
+        for root in 0..config.roots.len() {
+            let mut basin: Vec<(u32, u32)> = results.iter().zip(&canvas)
+                .filter(|(r, _)| r.converged && r.root_index == root)
+                .map(|(r, p)| (r.iterations, brightness(p)))
+                .collect();
+            basin.sort_unstable();
+            assert!(basin.windows(2).all(|w| w[0].1 >= w[1].1), "root {}", root);
+            assert!(basin[0].1 > basin[basin.len() - 1].1, "root {} is not shaded", root);
+        }
+        for (r, p) in results.iter().zip(&canvas).filter(|(r, _)| !r.converged) {
+            assert_eq!(*p, crate::coloring::shade(config.colors[r.root_index], 1.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn data_size_is_checked_against_the_rows() {
+        let huge = format!("width,height,scale,roots\n1000000,1000000,10,5\n{}\n0,3,1,0,0,-1,0\n", DATA_HEADER);
+        let err = read_data(&huge).err().unwrap();
+        assert!(err.contains("expected 1000000000000 pixels"), "{}", err);
+        let rows = "0,3,1,0,0,-1,0\n".repeat(2);
+        let extra = format!("width,height,scale,roots\n1,1,10,5\n{}\n{}", DATA_HEADER, rows);
+        assert!(read_data(&extra).err().unwrap().starts_with("line 5: more rows than the 1x1 image"));
+    }
+
+    #[test]
+    fn recolor_survives_a_hostile_iteration_count() {
+        let pol = Polynom::from_roots();
+        let der = pol.derivative();
+        let config = small().width(2).height(2).scale(1.0).coloring(Coloring::Histogram).build().unwrap();
+        let fast = "1,3,1,0,0,0,1\n".repeat(3);
+        let text = format!("width,height,scale,roots\n2,2,1,5\n{}\n0,4000000000,1,0,0,-1,0\n{}",
+                           DATA_HEADER, fast);
+        let read = read_data(&text).unwrap();
+        assert_eq!(read.results[0].iterations, 4_000_000_000);
+        let canvas = color_pixels(&pol, &der, &config, &read.results);
+        assert_eq!(canvas[0], crate::coloring::shade(config.colors[0], 0.75, 0.0));
+    }
+
+    #[test]
+    fn poly_round_trip_is_exact() {
+        let pol = Polynom::from_roots();
+        let mut text = Vec::new();
+        write_poly(&mut text, &pol, crate::ROOTS).unwrap();
+        let read = read_poly(&String::from_utf8(text).unwrap()).unwrap();
+        assert_eq!(read.pol, pol);
+        assert_eq!(read.roots.as_deref(), Some(crate::ROOTS));
+    }
+
+    #[test]
+    fn poly_roots_are_optional_but_must_match_the_degree() {
+        let read = read_poly("[[-1, 0], [0, 0], [1, 0]]\n").unwrap();
+        assert_eq!(read.pol.degree(), 2);
+        assert!(read.roots.is_none());
+        assert!(read_poly("[[-1, 0], [0, 0], [1, 0]]\n[[1, 0]]\n").is_err());
+    }
+
+    #[test]
+    fn stats_json_holds_the_render_summary() {
+        let pol = Polynom::from_roots();
+        let config = small().build().unwrap();
+        let results = classify_pixels(&pol, &pol.derivative(), &config).unwrap();
+        let stats = render_stats(&results, config.roots.len(), Duration::from_millis(1500));
+        let mut json = Vec::new();
+        write_stats_json(&mut json, &stats, &config).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        let field = |key: &str| {
+            let line = json.lines().find(|l| l.trim_start().starts_with(&format!("\"{}\":", key))).unwrap();
+            line.split_once(": ").unwrap().1.trim_end_matches(',').to_string()
+        };
+        let fractions: Vec<f64> = field("basin_fractions").trim_matches(['[', ']']).split(", ")
+            .map(|f| f.parse().unwrap())
+            .collect();
+        assert_eq!(fractions, stats.basin_fractions);
+        let unconverged: f64 = field("unconverged_fraction").parse().unwrap();
+        let total = fractions.iter().sum::<f64>() + unconverged;
+        assert!((total - 1.0).abs() < 1e-6, "fractions sum to {}", total);
+        assert_eq!(field("elapsed_seconds"), "1.5");
+        assert_eq!(field("width"), "80");
+        assert_eq!(field("max_iterations").parse::<u32>().unwrap(), stats.max_iterations);
+        assert!(stats.max_iterations <= config.steps && stats.mean_iterations > 0.0);
+        assert_eq!(json.matches('{').count(), json.matches('}').count());
+    }
+}
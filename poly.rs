@@ -0,0 +1,411 @@
+use num::complex::Complex;
+use num::Zero;
+use std::fmt;
+use std::ops::{Add, Mul, MulAssign};
+use std::str::FromStr;
+#[cfg(feature = "simd")]
+use std::simd::f32x8;
+use std::vec;
+
+use crate::ROOTS;
+
+const ROOT_FINDER_STEPS: u32 = 500;
+/// Highest degree `pow` and `compose` will build; both grow the degree
+/// multiplicatively, and far beyond this f32 coefficients are noise anyway.
+pub const MAX_DEGREE: usize = 4096;
+
+/// Points evaluated together by `at_simd`.
+#[cfg(feature = "simd")]
+pub const LANES: usize = 8;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polynom {
+    pub cs: Vec<Complex<f32>>,
+}
+
+impl MulAssign<Polynom> for Polynom {
+    fn mul_assign(&mut self, rhs: Polynom) {
+        *self *= &rhs;
+    }
+}
+
+impl MulAssign<&Polynom> for Polynom {
+    fn mul_assign(&mut self, rhs: &Polynom) {
+        *self = &*self * rhs;
+    }
+}
+
+impl Mul<&Polynom> for &Polynom {
+    type Output = Polynom;
+
+    fn mul(self, rhs: &Polynom) -> Polynom {
+        let len = self.cs.len() - 1 + rhs.cs.len() - 1 + 1;
+        let mut res = Polynom{cs:vec![Complex::zero(); len]};
+        for i in 0..self.cs.len() {
+            for j in 0..rhs.cs.len() {
+                res.cs[i+j] += self.cs[i] * rhs.cs[j];
+            }
+        }
+        res
+    }
+}
+
+impl Add<&Polynom> for &Polynom {
+    type Output = Polynom;
+
+    fn add(self, rhs: &Polynom) -> Polynom {
+        let len = self.cs.len().max(rhs.cs.len());
+        let mut res = Polynom{cs:vec![Complex::zero(); len]};
+        for (i, c) in self.cs.iter().enumerate() {
+            res.cs[i] += c;
+        }
+        for (i, c) in rhs.cs.iter().enumerate() {
+            res.cs[i] += c;
+        }
+        res
+    }
+}
+
+/// Collects roots into the polynomial that has them, as `from_root_iter`.
+impl FromIterator<Complex<f32>> for Polynom {
+    fn from_iter<I: IntoIterator<Item = Complex<f32>>>(roots: I) -> Self {
+        Polynom::from_root_iter(roots)
+    }
+}
+
+impl fmt::Display for Polynom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0")
+        }
+        for i in (0..=self.degree()).rev() {
+            write!(f, "{}: ({}) ", i, self.cs[i])?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a JSON array of `[re, im]` pairs, the form `output::write_poly`
+/// uses for both coefficients and roots.
+pub fn parse_pairs(s: &str) -> Result<Vec<Complex<f32>>, String> {
+    let malformed = || "expected a JSON array of [re, im] pairs".to_string();
+    let mut rest = s.trim()
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .ok_or_else(malformed)?
+        .trim();
+    let mut cs = Vec::new();
+    while !rest.is_empty() {
+        let pair = rest.strip_prefix('[').ok_or_else(malformed)?;
+        let end = pair.find(']').ok_or_else(malformed)?;
+        let (re, im) = pair[..end].split_once(',').ok_or_else(malformed)?;
+        let part = |p: &str| p.trim().parse::<f32>()
+            .map_err(|e| format!("invalid number '{}': {}", p.trim(), e));
+        cs.push(Complex::new(part(re)?, part(im)?));
+        rest = pair[end + 1..].trim_start();
+        if let Some(next) = rest.strip_prefix(',') {
+            rest = next.trim_start();
+            if rest.is_empty() {
+                return Err(malformed());
+            }
+        } else if !rest.is_empty() {
+            return Err(malformed());
+        }
+    }
+    Ok(cs)
+}
+
+/// Parses the coefficient list written by `output::write_poly`: a JSON
+/// array of `[re, im]` pairs, lowest degree first. The coefficients are
+/// kept as written, leading one included; only `to_monic` rescales them.
+impl FromStr for Polynom {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let cs = parse_pairs(s)?;
+        if cs.is_empty() {
+            return Err("a polynomial needs at least one coefficient".to_string());
+        }
+        Ok(Polynom{cs})
+    }
+}
+
+impl Polynom {
+
+    /// Index of the highest nonzero coefficient, ignoring trailing zeros.
+    /// The zero polynomial has degree 0.
+    pub fn degree(&self) -> usize {
+        self.cs.iter().rposition(|c| !c.is_zero()).unwrap_or(0)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.cs.iter().all(|c| c.is_zero())
+    }
+
+    pub fn at(&self, coord: Complex<f32>) -> Complex<f32> {
+        let mut res: Complex<f32> = Complex::zero();
+        for i in 0..self.cs.len() {
+            res += self.cs[i] * coord.powu(i as u32);
+        }
+        res
+    }
+
+    /// Evaluates at `coord` by Horner's rule; `--compare-eval` uses it to
+    /// measure `at`'s error.
+    pub fn horner(&self, coord: Complex<f32>) -> Complex<f32> {
+        self.cs.iter().rev().fold(Complex::zero(), |acc, &c| acc * coord + c)
+    }
+
+    /// Evaluates at `LANES` points at once, given as their real and
+    /// imaginary parts, by Horner's rule.
+    #[cfg(feature = "simd")]
+    pub fn at_simd(&self, re: f32x8, im: f32x8) -> (f32x8, f32x8) {
+        let (mut ar, mut ai) = (f32x8::splat(0.0), f32x8::splat(0.0));
+        for c in self.cs.iter().rev() {
+            (ar, ai) = (ar * re - ai * im + f32x8::splat(c.re),
+                        ar * im + ai * re + f32x8::splat(c.im));
+        }
+        (ar, ai)
+    }
+
+    /// Horner evaluation in double precision, as a reference for the f32 ones.
+    pub fn at_f64(&self, coord: Complex<f64>) -> Complex<f64> {
+        self.cs.iter().rev()
+            .fold(Complex::zero(), |acc, c| acc * coord + Complex::new(c.re as f64, c.im as f64))
+    }
+
+    /// The polynomial built from the roots in lib.rs.
+    pub fn from_roots() -> Polynom {
+        Polynom::from_root_iter(ROOTS.iter().cloned())
+    }
+
+    /// The monic polynomial with exactly the given roots, the product of
+    /// `z - root` over all of them.
+    pub fn from_root_iter(roots: impl IntoIterator<Item = Complex<f32>>) -> Polynom {
+        let mut pol = Polynom{
+            cs:vec![Complex::new(1.0, 0.0)]
+        };
+        for root in roots {
+            pol *= Polynom{cs:vec![-root, Complex::new(1.0, 0.0)]}
+        }
+        pol
+    }
+
+    /// `self(g(z))`, by Horner's rule over polynomials. The degree is
+    /// `degree() * g.degree()`; trailing zero coefficients of either are
+    /// dropped first so they cannot inflate the product. Fails if the degree
+    /// would exceed `MAX_DEGREE`.
+    pub fn compose(&self, g: &Polynom) -> Result<Polynom, String> {
+        check_degree(self.degree().checked_mul(g.degree()))?;
+        let g = g.trimmed();
+        let f = self.trimmed();
+        let (lead, rest) = f.cs.split_last().unwrap();
+        let mut res = Polynom{cs:vec![*lead]};
+        for &c in rest.iter().rev() {
+            res *= &g;
+            res = &res + &Polynom{cs:vec![c]};
+        }
+        Ok(res)
+    }
+
+    /// `self` multiplied by itself `k` times, by repeated squaring.
+    /// `pow(0)` is the constant 1. Fails if the degree would exceed
+    /// `MAX_DEGREE`.
+    pub fn pow(&self, k: u32) -> Result<Polynom, String> {
+        check_degree(self.degree().checked_mul(k as usize))?;
+        let mut res = Polynom{cs:vec![Complex::new(1.0, 0.0)]};
+        // Trailing zeros are dropped so they cannot double with every squaring.
+        let mut base = self.trimmed();
+        let mut k = k;
+        while k > 0 {
+            if k & 1 == 1 {
+                res *= &base;
+            }
+            k >>= 1;
+            if k > 0 {
+                base = &base * &base;
+            }
+        }
+        Ok(res)
+    }
+
+    /// The coefficients up to the degree, without trailing zeros; an empty
+    /// `cs` becomes the zero constant.
+    fn trimmed(&self) -> Polynom {
+        if self.cs.is_empty() {
+            return Polynom{cs:vec![Complex::zero()]}
+        }
+        Polynom{cs:self.cs[..=self.degree()].to_vec()}
+    }
+
+    /// Divides every coefficient by the leading one. Newton's `f/f'` is
+    /// unchanged by the scaling; the zero polynomial is returned as is.
+    pub fn to_monic(&self) -> Polynom {
+        if self.is_zero() {
+            return self.clone()
+        }
+        let lead = self.cs[self.degree()];
+        Polynom{cs:self.cs[..=self.degree()].iter().map(|c| c / lead).collect()}
+    }
+
+    /// Approximates all complex roots with the Durand-Kerner iteration.
+    pub fn find_roots(&self) -> Vec<Complex<f32>> {
+        let pol = self.to_monic();
+        let n = pol.degree();
+        let seed = Complex::new(0.4, 0.9);
+        let mut roots: Vec<Complex<f32>> = (0..n).map(|i| seed.powu(i as u32)).collect();
+        for _ in 0..ROOT_FINDER_STEPS {
+            let mut max_step: f32 = 0.0;
+            for i in 0..n {
+                let mut denom = Complex::new(1.0, 0.0);
+                for j in 0..n {
+                    if i != j {
+                        denom *= roots[i] - roots[j];
+                    }
+                }
+                let step = pol.at(roots[i]) / denom;
+                roots[i] -= step;
+                max_step = max_step.max(step.norm());
+            }
+            if max_step < f32::EPSILON {
+                break;
+            }
+        }
+        roots
+    }
+
+    pub fn derivative(&self) -> Polynom {
+        if self.degree() == 0 {
+            return Polynom{cs:vec![Complex::zero()]}
+        }
+        let mut res = Polynom{cs:self.cs[..=self.degree()].to_vec()};
+        for i in 0..res.cs.len()-1 {
+            res.cs[i] = Complex::new((i + 1) as f32, 0.0) * res.cs[i+1];
+        }
+        res.cs.truncate(res.cs.len()-1);
+        res
+    }
+}
+
+/// Checks a result degree computed with checked arithmetic against `MAX_DEGREE`.
+fn check_degree(degree: Option<usize>) -> Result<(), String> {
+    match degree {
+        Some(d) if d <= MAX_DEGREE => Ok(()),
+        Some(d) => Err(format!("degree {} exceeds the limit of {}", d, MAX_DEGREE)),
+        None => Err(format!("degree overflows, far beyond the limit of {}", MAX_DEGREE)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn real(cs: &[f32]) -> Polynom {
+        Polynom{cs: cs.iter().map(|&c| Complex::new(c, 0.0)).collect()}
+    }
+
+    #[test]
+    fn compose_has_no_trailing_zeros() {
+        assert_eq!(real(&[0.0, 0.0, 1.0]).compose(&real(&[1.0, 1.0])).unwrap(), real(&[1.0, 2.0, 1.0]));
+        assert_eq!(real(&[2.0, 0.0]).compose(&real(&[1.0, 1.0, 0.0])).unwrap(), real(&[2.0]));
+    }
+
+    #[test]
+    fn compose_refuses_degrees_beyond_the_limit() {
+        let square = real(&[0.0, 0.0, 1.0]);
+        let wide = square.pow(MAX_DEGREE as u32 / 4).unwrap();
+        assert_eq!(wide.compose(&square).unwrap().degree(), MAX_DEGREE);
+        assert!(wide.compose(&real(&[0.0, 0.0, 0.0, 1.0])).unwrap_err().contains("exceeds"));
+    }
+
+    #[test]
+    fn compose_treats_empty_coefficients_as_zero() {
+        let empty = Polynom{cs: Vec::new()};
+        assert_eq!(empty.compose(&real(&[1.0, 1.0])).unwrap(), real(&[0.0]));
+        assert_eq!(real(&[3.0, 2.0]).compose(&empty).unwrap(), real(&[3.0]));
+        assert_eq!(empty.compose(&empty).unwrap(), real(&[0.0]));
+    }
+
+    #[test]
+    fn roots_collect_into_their_polynomial() {
+        assert_eq!(ROOTS.iter().cloned().collect::<Polynom>(), Polynom::from_roots());
+        assert_eq!(Polynom::from_root_iter(ROOTS.to_vec()), Polynom::from_roots());
+        assert_eq!(std::iter::empty().collect::<Polynom>(), real(&[1.0]));
+        let pol: Polynom = [1.0, -1.0].iter().map(|&r| Complex::new(r, 0.0)).collect();
+        assert_eq!(pol, real(&[-1.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn single_root_gives_a_linear_factor() {
+        let root = Complex::new(0.5, -0.25);
+        let pol = Polynom::from_root_iter([root]);
+        assert_eq!(pol.cs, vec![-root, Complex::new(1.0, 0.0)]);
+        assert_eq!(pol.derivative(), real(&[1.0]));
+        assert_eq!(pol.at(root), Complex::zero());
+    }
+
+    #[test]
+    fn degree_ignores_trailing_zeros() {
+        assert_eq!(real(&[1.0, 2.0, 0.0, 0.0]).degree(), 1);
+        assert_eq!(real(&[0.0, 0.0]).degree(), 0);
+        assert!(real(&[0.0, 0.0]).is_zero() && !real(&[0.0, 1.0, 0.0]).is_zero());
+        assert_eq!(real(&[1.0, 2.0, 3.0, 0.0]).derivative(), real(&[2.0, 6.0]));
+        let sum = &real(&[1.0, 0.0, 1.0]) + &real(&[0.0, 1.0, -1.0]);
+        assert_eq!((sum.degree(), sum.cs.len()), (1, 3));
+        assert_eq!(sum.to_string(), real(&[1.0, 1.0]).to_string());
+    }
+
+    #[test]
+    fn products_by_reference_and_by_value_agree() {
+        let a = Polynom::from_roots();
+        let b = Polynom{cs: vec![Complex::new(1.0, 2.0), Complex::new(-0.5, 0.0)]};
+        let by_ref = &a * &b;
+        let mut by_value = a.clone();
+        by_value *= b.clone();
+        let mut assigned = a.clone();
+        assigned *= &b;
+        assert_eq!(by_ref, by_value);
+        assert_eq!(by_ref, assigned);
+        assert_eq!(by_ref.degree(), a.degree() + b.degree());
+    }
+
+    #[test]
+    fn pow_matches_repeated_products() {
+        let pol = Polynom{cs: vec![Complex::new(1.0, -1.0), Complex::new(0.5, 0.0), Complex::new(2.0, 1.0)]};
+        let pow = |k| pol.pow(k).unwrap();
+        assert_eq!(pow(0), real(&[1.0]));
+        assert_eq!(pow(1), pol);
+        assert_eq!(pow(2), &pol * &pol);
+        assert_eq!(pow(5), &(&pow(2) * &pow(2)) * &pol);
+        assert_eq!(pow(5).degree(), 10);
+    }
+
+    #[test]
+    fn pow_refuses_degrees_beyond_the_limit() {
+        let pol = real(&[1.0, 0.0, 1.0]);
+        assert_eq!(pol.pow(MAX_DEGREE as u32 / 2).unwrap().degree(), MAX_DEGREE);
+        let err = pol.pow(MAX_DEGREE as u32 / 2 + 1).unwrap_err();
+        assert!(err.contains("exceeds"), "{}", err);
+        assert!(pol.pow(u32::MAX).is_err());
+        assert_eq!(real(&[1.0, 0.0]).pow(u32::MAX).unwrap(), real(&[1.0]));
+        let empty = Polynom{cs: Vec::new()};
+        assert_eq!((empty.pow(0).unwrap(), empty.pow(3).unwrap()), (real(&[1.0]), real(&[0.0])));
+    }
+
+    #[test]
+    fn horner_is_no_less_accurate_than_naive() {
+        let pol = Polynom::from_roots();
+        assert_eq!(pol.degree(), 5);
+        let (mut naive, mut horner) = (0.0f64, 0.0f64);
+        for i in -20..=20 {
+            for j in -15..=15 {
+                let z = Complex::new(i as f32 / 5.0, j as f32 / 5.0);
+                let exact = pol.at_f64(Complex::new(z.re as f64, z.im as f64));
+                let err = |v: Complex<f32>| (Complex::new(v.re as f64, v.im as f64) - exact).norm();
+                naive = naive.max(err(pol.at(z)));
+                horner = horner.max(err(pol.horner(z)));
+            }
+        }
+        assert!(naive > 0.0 && horner <= naive, "Horner {:e}, naive {:e}", horner, naive);
+    }
+}
@@ -0,0 +1,1751 @@
+use num::complex::Complex;
+use num::Zero;
+#[cfg(feature = "simd")]
+use std::array;
+use std::error;
+use std::fmt;
+use std::f32::consts::TAU;
+use std::mem;
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+#[cfg(feature = "simd")]
+use std::simd::{cmp::{SimdPartialEq, SimdPartialOrd}, f32x8, mask32x8, num::SimdFloat, Select, StdFloat};
+
+use crate::color::{from_rgb, hashed_palette, palette, to_rgb, PalettePreset, Pixel};
+use crate::coloring::{dither_offset, summarize, Coloring, Summary};
+use crate::poly::Polynom;
+#[cfg(feature = "simd")]
+use crate::poly::LANES;
+use crate::{EPSILON, ESCAPE_RADIUS, MAX_MEMORY, PIXELS_PER_UNIT, PX_HEIGHT, PX_WIDTH, ROOTS, STEPS};
+
+const CYCLE_HISTORY: usize   = 8;
+const PROGRESS_STEPS: usize  = 100;
+const MASK_FOREGROUND: Pixel = 0xffffff;
+const BACKGROUND: Pixel      = 0x000000;
+const SWATCH_SIZE: i32       = 32;
+const SWATCH_GAP: i32        = 8;
+const LEGEND_COLUMNS: i32    = 8;
+const MIN_GRID_SPACING: f32  = 20.0;
+/// Starting-point offset, in units, between the darkest and brightest pixel
+/// of a seed image.
+const SEED_AMPLITUDE: f32    = 0.1;
+
+/// True if `x` lies in `[a, b]`, bounds included.
+pub fn between(x: f32, a: f32, b: f32) -> bool {
+    x >= a && x <= b
+}
+
+#[derive(Clone, Copy)]
+pub struct PixelResult {
+    pub root_index: usize,
+    pub iterations: u32,
+    pub converged: bool,
+    pub cycle: bool,
+    /// The orbit left the escape radius or stopped being finite.
+    pub escaped: bool,
+    pub z: Complex<f32>,
+}
+
+/// How pixels that never reach a root pick the basin they are colored by.
+#[derive(Clone, Copy)]
+pub enum Fallback {
+    /// The root nearest to the final point of the orbit.
+    Nearest,
+    /// The root whose argument is closest to the final point's.
+    Argument,
+}
+
+impl fmt::Display for Fallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fallback::Nearest  => write!(f, "nearest"),
+            Fallback::Argument => write!(f, "argument"),
+        }
+    }
+}
+
+impl FromStr for Fallback {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest"  => Ok(Fallback::Nearest),
+            "argument" => Ok(Fallback::Argument),
+            _ => Err("expected nearest or argument".to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Viewport {
+    pub width: i32,
+    pub height: i32,
+    /// Pixels per unit of the complex plane; need not be whole.
+    pub scale: f32,
+}
+
+/// The binary's historical image: `PX_WIDTH` by `PX_HEIGHT` at
+/// `PIXELS_PER_UNIT`.
+impl Default for Viewport {
+    fn default() -> Self {
+        Viewport{width: PX_WIDTH, height: PX_HEIGHT, scale: PIXELS_PER_UNIT as f32}
+    }
+}
+
+impl Viewport {
+    /// Column of the origin. Kept whole, even for a fractional `scale`, so
+    /// the origin always falls on a pixel center.
+    pub fn max_x(&self) -> i32 {
+        self.width / 2
+    }
+
+    pub fn max_y(&self) -> i32 {
+        self.height / 2
+    }
+
+    /// Offset of pixel `(x, y)` in a row-major buffer. Computed in `usize`
+    /// so large images cannot overflow `i32`; panics on pixels outside the
+    /// image rather than landing on the wrong row.
+    pub fn index(&self, x: i32, y: i32) -> usize {
+        assert!((0..self.width).contains(&x) && (0..self.height).contains(&y),
+                "pixel ({}, {}) is outside the {}x{} image", x, y, self.width, self.height);
+        y as usize * self.width as usize + x as usize
+    }
+
+    /// The point at pixel coordinates `(x, y)`, fractional for subpixel
+    /// samples. This is the one mapping from pixels to the plane: the render
+    /// loop, supersampling and `sample_grid` all go through it.
+    pub fn pixel_to_complex(&self, x: f32, y: f32) -> Complex<f32> {
+        Complex::new((x - self.max_x() as f32) / self.scale,
+                     (y - self.max_y() as f32) / self.scale)
+    }
+
+    /// True if `p` lies in the plotted region, `max_x / scale` by
+    /// `max_y / scale` units either side of the origin, edges included.
+    pub fn contains(&self, p: Complex<f32>) -> bool {
+        let mx = self.max_x() as f32 / self.scale;
+        let my = self.max_y() as f32 / self.scale;
+        between(p.re, -mx, mx) && between(p.im, -my, my)
+    }
+
+    /// Nearest pixel to `p`, or `None` if it falls outside the image.
+    pub fn complex_to_pixel(&self, p: Complex<f32>) -> Option<(i32, i32)> {
+        let x = (p.re * self.scale + self.max_x() as f32).round();
+        let y = (p.im * self.scale + self.max_y() as f32).round();
+        if between(x, 0.0, (self.width - 1) as f32) && between(y, 0.0, (self.height - 1) as f32) {
+            Some((x as i32, y as i32))
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RenderError {
+    InvalidSize { width: i32, height: i32 },
+    InvalidScale(f32),
+    InvalidEpsilon(f32),
+    InvalidEscapeRadius(f32),
+    NoRoots,
+    /// The polynomial is constant, so its derivative vanishes everywhere
+    /// and Newton's method never moves.
+    NoDynamics,
+    NotEnoughColors { roots: usize, colors: usize },
+    /// A color with bits set above the 24 of `RRGGBB`, which `to_rgb` would drop.
+    InvalidColor(Pixel),
+    RootOutOfBounds(Complex<f32>),
+    InvalidRootIndex { index: usize, roots: usize },
+    InvalidSeedImage { width: i32, height: i32, values: usize },
+    /// The canvas would need `bytes` (`None` if that overflows `usize`),
+    /// more than the allowed `limit`.
+    TooLarge { width: i32, height: i32, bytes: Option<usize>, limit: usize },
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::InvalidSize{width, height} =>
+                write!(f, "Image dimensions {}x{} must be positive", width, height),
+            RenderError::InvalidScale(scale) =>
+                write!(f, "Scale {} must be positive and finite", scale),
+            RenderError::InvalidEpsilon(eps) =>
+                write!(f, "Epsilon {} must be positive and finite", eps),
+            RenderError::InvalidEscapeRadius(radius) =>
+                write!(f, "Escape radius {} must be positive", radius),
+            RenderError::NoRoots =>
+                write!(f, "No roots specified"),
+            RenderError::NoDynamics =>
+                write!(f, "The polynomial is constant, so Newton's method has nothing to iterate"),
+            RenderError::NotEnoughColors{roots, colors} =>
+                write!(f, "Not enough colors to mark all roots ({} roots, {} colors)", roots, colors),
+            RenderError::InvalidColor(color) =>
+                write!(f, "Color {:#x} has more than 24 bits; alpha is not supported", color),
+            RenderError::RootOutOfBounds(root) =>
+                write!(f, "Root {} is out of image bounds", root),
+            RenderError::InvalidRootIndex{index, roots} =>
+                write!(f, "Root index {} is out of range, there are {} roots", index, roots),
+            RenderError::InvalidSeedImage{width, height, values} =>
+                write!(f, "A {}x{} seed image needs a value per pixel, got {}", width, height, values),
+            RenderError::TooLarge{width, height, bytes: Some(bytes), limit} =>
+                write!(f, "A {}x{} image needs {} bytes, more than the {} byte limit (see --max-memory)",
+                       width, height, bytes, limit),
+            RenderError::TooLarge{width, height, bytes: None, limit} =>
+                write!(f, "A {}x{} image needs more than {} bytes, over the {} byte limit (see --max-memory)",
+                       width, height, usize::MAX, limit),
+        }
+    }
+}
+
+impl error::Error for RenderError {}
+
+/// Checks that Newton's method moves at all, i.e. that `der`, the derivative
+/// of the polynomial being iterated, is not identically zero.
+pub fn check_dynamics(der: &Polynom) -> Result<(), RenderError> {
+    if der.is_zero() {
+        return Err(RenderError::NoDynamics);
+    }
+    Ok(())
+}
+
+/// Checks that the canvas and per-pixel results for `view` fit in `limit` bytes.
+pub fn check_memory(view: &Viewport, limit: usize) -> Result<(), RenderError> {
+    let per_pixel = mem::size_of::<Pixel>() + mem::size_of::<PixelResult>();
+    let bytes = (view.width as usize)
+        .checked_mul(view.height as usize)
+        .and_then(|n| n.checked_mul(per_pixel));
+    match bytes {
+        Some(bytes) if bytes <= limit => Ok(()),
+        _ => Err(RenderError::TooLarge{width: view.width, height: view.height, bytes, limit}),
+    }
+}
+
+#[derive(Clone)]
+pub struct RenderConfig {
+    pub view: Viewport,
+    pub roots: Vec<Complex<f32>>,
+    pub colors: Vec<Pixel>,
+    pub steps: u32,
+    pub epsilon: f32,
+    pub escape_radius: f32,
+    pub patience: u32,
+    pub aa: u32,
+    pub cycle_color: Pixel,
+    pub cycle_tolerance: f32,
+    /// Color of escaped orbits; `None` colors them by their fallback basin.
+    pub divergent_color: Option<Pixel>,
+    pub coloring: Coloring,
+    /// Colors above and below the real axis for `Coloring::TwoTone`.
+    pub tones: (Pixel, Pixel),
+    pub fallback: Fallback,
+    pub symmetry: bool,
+    pub dither: bool,
+    /// Renders a mask of this root's basin instead of the palette.
+    pub only_root: Option<usize>,
+    /// Per-pixel shifts of the starting points, in row-major order.
+    pub offsets: Option<Vec<Complex<f32>>>,
+    /// Threads each pass runs on; 0 uses every available core.
+    pub threads: usize,
+}
+
+impl RenderConfig {
+    /// Where Newton's method starts for the point `(px, py)` inside pixel `(x, y)`.
+    fn start(&self, x: i32, y: i32, px: f32, py: f32) -> Complex<f32> {
+        let ic = self.view.pixel_to_complex(px, py);
+        match &self.offsets {
+            Some(offsets) => ic + offsets[self.view.index(x, y)],
+            None => ic,
+        }
+    }
+}
+
+impl fmt::Display for RenderConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let roots: Vec<String> = self.roots.iter().map(|r| r.to_string()).collect();
+        let colors: Vec<String> = self.colors.iter().map(|c| format!("#{:06x}", c)).collect();
+        writeln!(f, "Size: {}x{} at {} px/unit", self.view.width, self.view.height, self.view.scale)?;
+        writeln!(f, "Roots: {}", roots.join(", "))?;
+        writeln!(f, "Colors: {}", colors.join(", "))?;
+        writeln!(f, "Max iterations: {}, epsilon: {}", self.steps, self.epsilon)?;
+        writeln!(f, "Escape radius: {}", self.escape_radius)?;
+        if self.patience > 0 {
+            writeln!(f, "Patience: {}", self.patience)?;
+        }
+        writeln!(f, "Adaptive AA: {}", self.aa)?;
+        writeln!(f, "Coloring: {}", self.coloring)?;
+        if let Coloring::TwoTone = self.coloring {
+            writeln!(f, "Tones: #{:06x}, #{:06x}", self.tones.0, self.tones.1)?;
+        }
+        writeln!(f, "Fallback: {}", self.fallback)?;
+        writeln!(f, "Dither: {}", self.dither)?;
+        if let Some(index) = self.only_root {
+            writeln!(f, "Only root: {} ({})", index, self.roots[index])?;
+        }
+        if self.offsets.is_some() {
+            writeln!(f, "Starting points: shifted by a seed image")?;
+        }
+        writeln!(f, "Exploit symmetry: {}", self.symmetry)?;
+        match self.threads {
+            0 => writeln!(f, "Threads: all cores")?,
+            n => writeln!(f, "Threads: {}", n)?,
+        }
+        if self.cycle_tolerance > 0.0 {
+            writeln!(f, "Cycles: tolerance {}, color #{:06x}", self.cycle_tolerance, self.cycle_color)?;
+        } else {
+            writeln!(f, "Cycles: off")?;
+        }
+        match self.divergent_color {
+            Some(color) => write!(f, "Divergent color: #{:06x}", color),
+            None => write!(f, "Divergent color: fallback basin"),
+        }
+    }
+}
+
+/// Collects render settings, filling in the historical defaults, and
+/// validates them all at once in `build`.
+#[derive(Clone)]
+pub struct RenderConfigBuilder {
+    view: Viewport,
+    roots: Vec<Complex<f32>>,
+    palette: PalettePreset,
+    colors: Option<Vec<Pixel>>,
+    hashed_palette: bool,
+    steps: u32,
+    epsilon: f32,
+    escape_radius: f32,
+    patience: u32,
+    aa: u32,
+    cycle_color: Pixel,
+    cycle_tolerance: f32,
+    divergent_color: Option<Pixel>,
+    coloring: Coloring,
+    tones: (Pixel, Pixel),
+    fallback: Fallback,
+    symmetry: bool,
+    dither: bool,
+    only_root: Option<usize>,
+    seed_image: Option<(i32, i32, Vec<u8>)>,
+    max_memory: usize,
+    threads: usize,
+}
+
+/// The configuration the binary renders without options: the roots and
+/// colors of lib.rs, `STEPS` iterations and the default viewport.
+impl Default for RenderConfig {
+    fn default() -> Self {
+        RenderConfigBuilder::new().build().expect("the built-in defaults are valid")
+    }
+}
+
+impl Default for RenderConfigBuilder {
+    fn default() -> Self {
+        RenderConfigBuilder::new()
+    }
+}
+
+impl RenderConfigBuilder {
+    pub fn new() -> RenderConfigBuilder {
+        RenderConfigBuilder{
+            view: Viewport::default(),
+            roots: ROOTS.to_vec(),
+            palette: PalettePreset::Classic,
+            colors: None,
+            hashed_palette: false,
+            steps: STEPS as u32,
+            epsilon: EPSILON,
+            escape_radius: ESCAPE_RADIUS,
+            patience: 0,
+            aa: 1,
+            cycle_color: 0x000000,
+            cycle_tolerance: 0.0,
+            divergent_color: None,
+            coloring: Coloring::Basin,
+            tones: (0xffffff, 0x000000),
+            fallback: Fallback::Nearest,
+            symmetry: false,
+            dither: false,
+            only_root: None,
+            seed_image: None,
+            max_memory: MAX_MEMORY,
+            threads: 0,
+        }
+    }
+
+    pub fn width(&mut self, width: i32) -> &mut Self {
+        self.view.width = width;
+        self
+    }
+
+    pub fn height(&mut self, height: i32) -> &mut Self {
+        self.view.height = height;
+        self
+    }
+
+    pub fn scale(&mut self, scale: f32) -> &mut Self {
+        self.view.scale = scale;
+        self
+    }
+
+    pub fn roots(&mut self, roots: Vec<Complex<f32>>) -> &mut Self {
+        self.roots = roots;
+        self
+    }
+
+    pub fn palette(&mut self, palette: PalettePreset) -> &mut Self {
+        self.palette = palette;
+        self
+    }
+
+    /// Colors the basins with `colors`, in root order, instead of a preset.
+    pub fn colors(&mut self, colors: Vec<Pixel>) -> &mut Self {
+        self.colors = Some(colors);
+        self
+    }
+
+    /// Generates the palette from a hash of the roots instead of a preset, so
+    /// the same roots always get the same colors.
+    pub fn hashed_palette(&mut self, hashed: bool) -> &mut Self {
+        self.hashed_palette = hashed;
+        self
+    }
+
+    pub fn steps(&mut self, steps: u32) -> &mut Self {
+        self.steps = steps;
+        self
+    }
+
+    pub fn epsilon(&mut self, epsilon: f32) -> &mut Self {
+        self.epsilon = epsilon;
+        self
+    }
+
+    /// Orbits leaving the disc of this radius stop iterating and are
+    /// treated as divergent.
+    pub fn escape_radius(&mut self, radius: f32) -> &mut Self {
+        self.escape_radius = radius;
+        self
+    }
+
+    /// Gives up on orbits whose step has not shrunk for `iterations` steps
+    /// in a row; 0 disables the check.
+    pub fn patience(&mut self, iterations: u32) -> &mut Self {
+        self.patience = iterations;
+        self
+    }
+
+    pub fn aa(&mut self, factor: u32) -> &mut Self {
+        self.aa = factor;
+        self
+    }
+
+    pub fn cycle_color(&mut self, color: Pixel) -> &mut Self {
+        self.cycle_color = color;
+        self
+    }
+
+    /// Orbits returning within `tolerance` of an earlier point are colored
+    /// as cycles; 0 disables the check.
+    pub fn cycle_tolerance(&mut self, tolerance: f32) -> &mut Self {
+        self.cycle_tolerance = tolerance;
+        self
+    }
+
+    /// Colors orbits that escape, as opposed to merely not converging, with
+    /// `color` instead of their fallback basin's color.
+    pub fn divergent_color(&mut self, color: Pixel) -> &mut Self {
+        self.divergent_color = Some(color);
+        self
+    }
+
+    pub fn coloring(&mut self, coloring: Coloring) -> &mut Self {
+        self.coloring = coloring;
+        self
+    }
+
+    /// Switches to `Coloring::TwoTone` with `above` and `below` as the tones.
+    pub fn two_tone(&mut self, above: Pixel, below: Pixel) -> &mut Self {
+        self.coloring = Coloring::TwoTone;
+        self.tones = (above, below);
+        self
+    }
+
+    pub fn fallback(&mut self, fallback: Fallback) -> &mut Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Mirrors the lower half of the image from the upper one when the roots
+    /// are closed under conjugation; other root sets render in full.
+    pub fn symmetry(&mut self, symmetry: bool) -> &mut Self {
+        self.symmetry = symmetry;
+        self
+    }
+
+    /// Applies an ordered dither when shaded colors are rounded to 8 bits.
+    pub fn dither(&mut self, dither: bool) -> &mut Self {
+        self.dither = dither;
+        self
+    }
+
+    /// Colors pixels converging to root `index` white and all others black.
+    pub fn only_root(&mut self, index: usize) -> &mut Self {
+        self.only_root = Some(index);
+        self
+    }
+
+    /// Shifts each pixel's starting point along the real axis by how much
+    /// brighter or darker than average the matching pixel of a `width` by
+    /// `height` grayscale image is. The image is resampled to the render
+    /// size, and a uniform one changes nothing.
+    pub fn seed_image(&mut self, width: i32, height: i32, values: Vec<u8>) -> &mut Self {
+        self.seed_image = Some((width, height, values));
+        self
+    }
+
+    pub fn max_memory(&mut self, bytes: usize) -> &mut Self {
+        self.max_memory = bytes;
+        self
+    }
+
+    /// Runs each pass on `threads` threads, 1 for a serial render; 0, the
+    /// default, uses every available core.
+    pub fn threads(&mut self, threads: usize) -> &mut Self {
+        self.threads = threads;
+        self
+    }
+
+    pub fn build(&self) -> Result<RenderConfig, RenderError> {
+        let view = self.view;
+        if view.width <= 0 || view.height <= 0 {
+            return Err(RenderError::InvalidSize{width: view.width, height: view.height});
+        }
+        if !(view.scale > 0.0 && view.scale.is_finite()) {
+            return Err(RenderError::InvalidScale(view.scale));
+        }
+        if !(self.epsilon > 0.0 && self.epsilon.is_finite()) {
+            return Err(RenderError::InvalidEpsilon(self.epsilon));
+        }
+        if self.escape_radius.is_nan() || self.escape_radius <= 0.0 {
+            return Err(RenderError::InvalidEscapeRadius(self.escape_radius));
+        }
+        check_memory(&view, self.max_memory)?;
+        if self.roots.is_empty() {
+            return Err(RenderError::NoRoots);
+        }
+        let colors = match &self.colors {
+            Some(colors) => colors.clone(),
+            None if self.hashed_palette => hashed_palette(&self.roots),
+            None => palette(self.palette, self.roots.len()),
+        };
+        if colors.len() < self.roots.len() {
+            return Err(RenderError::NotEnoughColors{roots: self.roots.len(), colors: colors.len()});
+        }
+        let settings = [self.cycle_color, self.tones.0, self.tones.1].into_iter().chain(self.divergent_color);
+        if let Some(color) = colors.iter().cloned().chain(settings).find(|&c| c > 0xffffff) {
+            return Err(RenderError::InvalidColor(color));
+        }
+        if let Some(root) = self.roots.iter().find(|&&r| !view.contains(r)) {
+            return Err(RenderError::RootOutOfBounds(*root));
+        }
+        if let Some(index) = self.only_root.filter(|&i| i >= self.roots.len()) {
+            return Err(RenderError::InvalidRootIndex{index, roots: self.roots.len()});
+        }
+        if let Some((width, height, values)) = &self.seed_image {
+            if *width <= 0 || *height <= 0 || values.len() != *width as usize * *height as usize {
+                return Err(RenderError::InvalidSeedImage{width: *width, height: *height, values: values.len()});
+            }
+        }
+        Ok(RenderConfig{
+            view,
+            roots: self.roots.clone(),
+            colors,
+            steps: self.steps,
+            epsilon: self.epsilon,
+            escape_radius: self.escape_radius,
+            patience: self.patience,
+            aa: self.aa,
+            cycle_color: self.cycle_color,
+            cycle_tolerance: self.cycle_tolerance,
+            divergent_color: self.divergent_color,
+            coloring: self.coloring,
+            tones: self.tones,
+            fallback: self.fallback,
+            symmetry: self.symmetry,
+            dither: self.dither,
+            only_root: self.only_root,
+            offsets: self.seed_image.as_ref().map(|(w, h, values)| seed_offsets(&view, *w, *h, values)),
+            threads: self.threads,
+        })
+    }
+}
+
+/// Nearest-neighbor resamples the seed image to `view` and turns each value
+/// into a real offset around the image's mean.
+fn seed_offsets(view: &Viewport, width: i32, height: i32, values: &[u8]) -> Vec<Complex<f32>> {
+    // Summed exactly, so a uniform image has offsets of exactly zero.
+    let mean = (values.iter().map(|&v| v as u64).sum::<u64>() as f64 / values.len().max(1) as f64) as f32;
+    let mut offsets = Vec::with_capacity(view.width as usize * view.height as usize);
+    for y in 0..view.height {
+        for x in 0..view.width {
+            let sx = (x as i64 * width as i64 / view.width as i64) as usize;
+            let sy = (y as i64 * height as i64 / view.height as i64) as usize;
+            let v = values[sy * width as usize + sx] as f32;
+            offsets.push(Complex::new(SEED_AMPLITUDE * (v - mean) / 255.0, 0.0));
+        }
+    }
+    offsets
+}
+
+/// True if `c` is within `tolerance` of the orbit point two or more steps
+/// back, where `history[k % len]` holds the `k`-th point.
+fn revisits(history: &[Complex<f32>], iterations: u32, c: Complex<f32>, tolerance: f32) -> bool {
+    let n = iterations as usize;
+    (2..=n.min(history.len())).any(|lag| (c - history[(n - lag) % history.len()]).norm() < tolerance)
+}
+
+/// Runs Newton's method from `ic`. Orbits that leave `config.escape_radius`
+/// or overflow to infinity or NaN are marked `escaped`; the non-finite ones
+/// are assigned to root 0, whatever `config.fallback` is.
+pub fn classify(pol: &Polynom, der: &Polynom, config: &RenderConfig, ic: Complex<f32>) -> PixelResult {
+    classify_counting(pol, der, config, ic, &mut 0)
+}
+
+/// `classify`, adding the polynomial evaluations it makes to `evaluations`.
+fn classify_counting(pol: &Polynom, der: &Polynom, config: &RenderConfig, ic: Complex<f32>,
+                     evaluations: &mut u64) -> PixelResult {
+    let mut c = ic;
+    let mut iterations = 0;
+    let (mut cycle, mut escaped) = (false, false);
+    let mut history = [Complex::zero(); CYCLE_HISTORY];
+    let (mut last_step, mut stalled) = (f32::INFINITY, 0);
+    while iterations < config.steps {
+        let (yp, yd) = (pol.at(c), der.at(c));
+        *evaluations += 2;
+        if !c.is_finite() {
+            escaped = true;
+            break;
+        }
+        if yd == Complex::zero() {
+            break;
+        }
+        history[iterations as usize % CYCLE_HISTORY] = c;
+        let step = yp / yd;
+        c -= step;
+        iterations += 1;
+        if let Some(i) = config.roots.iter().position(|r| (c - r).norm() < config.epsilon) {
+            return PixelResult{root_index: i, iterations, converged: true, cycle: false, escaped: false, z: c}
+        }
+        if c.norm() > config.escape_radius {
+            escaped = true;
+            break;
+        }
+        if config.patience > 0 {
+            stalled = if step.norm() < last_step { 0 } else { stalled + 1 };
+            last_step = step.norm();
+            if stalled >= config.patience {
+                break;
+            }
+        }
+        if config.cycle_tolerance > 0.0 && revisits(&history, iterations, c, config.cycle_tolerance) {
+            cycle = true;
+            break;
+        }
+    }
+    if !c.is_finite() {
+        // Distances and angles to an infinite or NaN point mean nothing.
+        return PixelResult{root_index: 0, iterations, converged: false, cycle, escaped: true, z: c}
+    }
+
+    let dists: Vec<f32> = match config.fallback {
+        Fallback::Nearest  => config.roots.iter().map(|r| (c - r).norm()).collect(),
+        Fallback::Argument => config.roots.iter().map(|r| angle_between(c.arg(), r.arg())).collect(),
+    };
+    let mut index = 0;
+    let mut min = f32::INFINITY;
+    for (i, &dist) in dists.iter().enumerate() {
+        if dist < min {
+            min = dist;
+            index = i;
+        }
+    }
+    PixelResult{root_index: index, iterations, converged: false, cycle, escaped, z: c}
+}
+
+/// `classify` for `LANES` starting points in lockstep, for the plain
+/// iteration without `patience` or cycle detection. Lanes that meet a zero
+/// derivative, a point that is not finite or the escape radius, or are still
+/// unconverged after `config.steps`, are left `None` for `classify` to redo.
+/// Evaluation is by Horner's rule, so basin edges can differ slightly from
+/// `Polynom::at`'s.
+#[cfg(feature = "simd")]
+fn classify_lanes(pol: &Polynom, der: &Polynom, config: &RenderConfig,
+                  ics: [Complex<f32>; LANES], evaluations: &mut u64) -> [Option<PixelResult>; LANES] {
+    let mut re = f32x8::from_array(ics.map(|c| c.re));
+    let mut im = f32x8::from_array(ics.map(|c| c.im));
+    let mut results = [None; LANES];
+    let mut active = mask32x8::splat(true);
+    for iterations in 1..=config.steps {
+        let (pr, pi) = pol.at_simd(re, im);
+        let (dr, di) = der.at_simd(re, im);
+        *evaluations += 2 * LANES as u64;
+        let norm_sqr = dr * dr + di * di;
+        active &= norm_sqr.simd_ne(f32x8::splat(0.0)) & re.is_finite() & im.is_finite();
+        re = active.select(re - (pr * dr + pi * di) / norm_sqr, re);
+        im = active.select(im - (pi * dr - pr * di) / norm_sqr, im);
+        for (i, r) in config.roots.iter().enumerate() {
+            let (dx, dy) = (re - f32x8::splat(r.re), im - f32x8::splat(r.im));
+            let hit = active & (dx * dx + dy * dy).sqrt().simd_lt(f32x8::splat(config.epsilon));
+            let (res, ims) = (re.to_array(), im.to_array());
+            for (lane, result) in results.iter_mut().enumerate().filter(|&(lane, _)| hit.test(lane)) {
+                let z = Complex::new(res[lane], ims[lane]);
+                *result = Some(PixelResult{root_index: i, iterations, converged: true, cycle: false,
+                                           escaped: false, z});
+            }
+            active &= !hit;
+        }
+        active &= (re * re + im * im).sqrt().simd_le(f32x8::splat(config.escape_radius));
+        if !active.any() {
+            break;
+        }
+    }
+    results
+}
+
+/// Classifies row `y` of `config.view`. With the `simd` feature and neither
+/// `patience` nor cycle detection, pixels go through `classify_lanes`
+/// `LANES` at a time and only those it leaves are iterated one by one.
+fn classify_row(pol: &Polynom, der: &Polynom, config: &RenderConfig, y: i32,
+                evaluations: &mut u64) -> Vec<PixelResult> {
+    let width = config.view.width;
+    let start = |x: i32| config.start(x, y, x as f32, y as f32);
+    #[cfg(feature = "simd")]
+    if config.patience == 0 && config.cycle_tolerance <= 0.0 {
+        let mut row = Vec::with_capacity(width as usize);
+        for x0 in (0..width).step_by(LANES) {
+            let xs: [i32; LANES] = array::from_fn(|i| (x0 + i as i32).min(width - 1));
+            let lanes = classify_lanes(pol, der, config, xs.map(start), evaluations);
+            for (x, r) in xs.into_iter().zip(lanes).take((width - x0) as usize) {
+                row.push(r.unwrap_or_else(|| classify_counting(pol, der, config, start(x), evaluations)));
+            }
+        }
+        return row;
+    }
+    (0..width).map(|x| classify_counting(pol, der, config, start(x), evaluations)).collect()
+}
+
+/// Smallest angle between two directions, in `[0, pi]`.
+fn angle_between(a: f32, b: f32) -> f32 {
+    let d = (a - b).rem_euclid(TAU);
+    d.min(TAU - d)
+}
+
+/// Color of pixel `(x, y)`, classified as `r`.
+pub fn pixel_color(config: &RenderConfig, summary: &Summary, r: &PixelResult, x: i32, y: i32) -> Pixel {
+    if let Some(index) = config.only_root {
+        if r.converged && r.root_index == index { MASK_FOREGROUND } else { BACKGROUND }
+    } else if r.root_index >= config.roots.len().min(config.colors.len()) {
+        // Only reachable with a hand-built config that has no roots or colors.
+        BACKGROUND
+    } else if r.cycle {
+        config.cycle_color
+    } else if let (true, Some(color)) = (r.escaped, config.divergent_color) {
+        color
+    } else {
+        config.coloring.strategy().color(config, summary, r, dither_offset(config, x, y))
+    }
+}
+
+/// True if any 4-neighbor of `(x, y)` lies in a different basin.
+fn is_edge(view: &Viewport, results: &[PixelResult], x: i32, y: i32) -> bool {
+    let own = results[view.index(x, y)].root_index;
+    [(-1, 0), (1, 0), (0, -1), (0, 1)].iter().any(|(dx, dy)| {
+        let (nx, ny) = (x + dx, y + dy);
+        (0..view.width).contains(&nx) && (0..view.height).contains(&ny)
+            && results[view.index(nx, ny)].root_index != own
+    })
+}
+
+/// Averages `aa * aa` evenly spaced samples inside pixel `(x, y)`.
+fn supersample(pol: &Polynom, der: &Polynom, config: &RenderConfig, summary: &Summary,
+               x: i32, y: i32, evaluations: &mut u64) -> Pixel {
+    let factor = config.aa;
+    let (mut r, mut g, mut b) = (0u32, 0u32, 0u32);
+    for sy in 0..factor {
+        for sx in 0..factor {
+            let ox = (sx as f32 + 0.5) / factor as f32 - 0.5;
+            let oy = (sy as f32 + 0.5) / factor as f32 - 0.5;
+            let ic = config.start(x, y, x as f32 + ox, y as f32 + oy);
+            let sample = classify_counting(pol, der, config, ic, evaluations);
+            let (pr, pg, pb) = to_rgb(&pixel_color(config, summary, &sample, x, y));
+            r += pr as u32;
+            g += pg as u32;
+            b += pb as u32;
+        }
+    }
+    let n = factor * factor;
+    from_rgb(((r + n / 2) / n) as u8, ((g + n / 2) / n) as u8, ((b + n / 2) / n) as u8)
+}
+
+/// `width` by `height` points spread evenly over `view`, in row-major order
+/// with their column and row. The first and last points are the centers of
+/// the corner pixels, so a grid of `view`'s own size gives its pixels
+/// exactly; a single column or row runs through the origin. For diagnostics
+/// such as `--compare-eval`; the render loop calls `pixel_to_complex` per
+/// pixel itself.
+pub fn sample_grid(view: &Viewport, width: usize, height: usize)
+                   -> impl Iterator<Item = (usize, usize, Complex<f32>)> + '_ {
+    let coord = |i: usize, n: usize, pixels: i32, origin: i32| {
+        if n > 1 {
+            i as f32 * (pixels - 1) as f32 / (n - 1) as f32
+        } else {
+            origin as f32
+        }
+    };
+    (0..height).flat_map(move |j| (0..width).map(move |i| {
+        let z = view.pixel_to_complex(coord(i, width, view.width, view.max_x()),
+                                      coord(j, height, view.height, view.max_y()));
+        (i, j, z)
+    }))
+}
+
+/// Fractional iteration count of a classified pixel, scaled to `[0, 1]` by
+/// `config.steps`. Converged pixels interpolate between their last two
+/// steps from how far below `epsilon` the final distance fell, assuming the
+/// quadratic convergence of Newton's method; the rest get 1.
+pub fn smooth_iterations(config: &RenderConfig, r: &PixelResult) -> f32 {
+    if !r.converged || config.steps == 0 {
+        return 1.0;
+    }
+    let n = r.iterations as f32;
+    let dist = (r.z - config.roots[r.root_index]).norm();
+    let frac = if dist > 0.0 {
+        (dist.ln() / config.epsilon.ln()).log2().clamp(0.0, 1.0)
+    } else {
+        1.0
+    };
+    ((n - frac) / config.steps as f32).clamp(0.0, 1.0)
+}
+
+/// `smooth_iterations` of every pixel of a classify pass, in row-major order.
+pub fn smooth_field(config: &RenderConfig, results: &[PixelResult]) -> Vec<f32> {
+    results.iter().map(|r| smooth_iterations(config, r)).collect()
+}
+
+/// Evaluates `f` at every pixel of `view` in row-major order, giving each of
+/// `threads` threads (0 for every available one) one contiguous band of
+/// rows, so with 1 the rows run in order on a single thread. The calling
+/// thread reports the fraction of finished rows to `on_progress`, at most
+/// `PROGRESS_STEPS` times and last with exactly 1. `f` counts its polynomial
+/// evaluations in its thread's tally, and the tallies are summed and
+/// returned with the pixels.
+fn map_pixels<T: Send>(view: &Viewport, threads: usize, f: impl Fn(i32, i32, &mut u64) -> T + Sync,
+                       on_progress: impl FnMut(f32)) -> (Vec<T>, u64) {
+    map_rows(view, threads, |y, evaluations| (0..view.width).map(|x| f(x, y, evaluations)).collect(),
+             on_progress)
+}
+
+/// `map_pixels` for a function that produces whole rows of `view.width`.
+fn map_rows<T: Send>(view: &Viewport, threads: usize, f: impl Fn(i32, &mut u64) -> Vec<T> + Sync,
+                     mut on_progress: impl FnMut(f32)) -> (Vec<T>, u64) {
+    let threads = match threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+    let band = (view.height as usize).div_ceil(threads).max(1);
+    let f = &f;
+    let (tx, rx) = mpsc::channel();
+    thread::scope(|s| {
+        let bands: Vec<_> = (0..view.height).step_by(band).map(|y0| {
+            let tx = tx.clone();
+            s.spawn(move || {
+                let y1 = (y0 + band as i32).min(view.height);
+                let mut out = Vec::with_capacity((y1 - y0) as usize * view.width as usize);
+                let mut evaluations = 0;
+                for y in y0..y1 {
+                    out.extend(f(y, &mut evaluations));
+                    let _ = tx.send(());
+                }
+                (out, evaluations)
+            })
+        }).collect();
+        drop(tx);
+        let rows = view.height as usize;
+        let (mut done, mut step) = (0, 0);
+        for () in rx {
+            done += 1;
+            if done * PROGRESS_STEPS / rows > step {
+                step = done * PROGRESS_STEPS / rows;
+                on_progress(done as f32 / rows as f32);
+            }
+        }
+        let mut pixels = Vec::with_capacity(rows * view.width as usize);
+        let mut evaluations = 0;
+        for band in bands {
+            let (out, n) = band.join().unwrap();
+            pixels.extend(out);
+            evaluations += n;
+        }
+        (pixels, evaluations)
+    })
+}
+
+/// For each root, the index of its complex conjugate, or `None` if the set
+/// is not closed under conjugation.
+fn conjugate_indices(roots: &[Complex<f32>], tolerance: f32) -> Option<Vec<usize>> {
+    roots.iter()
+        .map(|r| roots.iter().position(|s| (s - r.conj()).norm() < tolerance))
+        .collect()
+}
+
+/// First pass: classifies every pixel of `config.view`, in row-major order.
+/// With `config.symmetry`, a nonempty conjugate-closed root set and no seed
+/// image only rows up to the real axis are iterated; the rest are their
+/// mirror images, with each basin swapped for its conjugate's. Fails with
+/// `RenderError::NoDynamics` if `der` is zero, as `check_dynamics`.
+pub fn classify_pixels(pol: &Polynom, der: &Polynom, config: &RenderConfig)
+                       -> Result<Vec<PixelResult>, RenderError> {
+    Ok(classify_pixels_with_progress(pol, der, config, |_| {})?.0)
+}
+
+/// `classify_pixels`, reporting the finished fraction to `on_progress` and
+/// returning the number of polynomial evaluations with the results.
+pub fn classify_pixels_with_progress(pol: &Polynom, der: &Polynom, config: &RenderConfig,
+                                     on_progress: impl FnMut(f32))
+                                     -> Result<(Vec<PixelResult>, u64), RenderError> {
+    check_dynamics(der)?;
+    let view = &config.view;
+    let classify_at = |y: i32, evaluations: &mut u64| classify_row(pol, der, config, y, evaluations);
+    let conj = match conjugate_indices(&config.roots, config.epsilon) {
+        Some(conj) if config.symmetry && config.offsets.is_none() && !config.roots.is_empty() => conj,
+        _ => return Ok(map_rows(view, config.threads, classify_at, on_progress)),
+    };
+    let axis = view.max_y();
+    let half = Viewport{height: axis + 1, ..*view};
+    let (mut results, evaluations) = map_rows(&half, config.threads, classify_at, on_progress);
+    for y in axis + 1..view.height {
+        for x in 0..view.width {
+            let r = results[view.index(x, 2 * axis - y)];
+            results.push(PixelResult{root_index: conj[r.root_index], z: r.z.conj(), ..r});
+        }
+    }
+    Ok((results, evaluations))
+}
+
+/// Second pass: reduces `results` to a `Summary`, then colors every pixel with
+/// `config.coloring`, supersampling basin edges when `config.aa > 1`.
+pub fn color_pixels(pol: &Polynom, der: &Polynom, config: &RenderConfig,
+                    results: &[PixelResult]) -> Vec<Pixel> {
+    color_pixels_with_progress(pol, der, config, results, |_| {}).0
+}
+
+/// `color_pixels`, reporting the finished fraction to `on_progress` and
+/// returning the number of polynomial evaluations supersampling made.
+pub fn color_pixels_with_progress(pol: &Polynom, der: &Polynom, config: &RenderConfig,
+                                  results: &[PixelResult], on_progress: impl FnMut(f32))
+                                  -> (Vec<Pixel>, u64) {
+    let view = &config.view;
+    let summary = summarize(config, results);
+    map_pixels(view, config.threads, |x, y, evaluations| {
+        if config.aa > 1 && is_edge(view, results, x, y) {
+            supersample(pol, der, config, &summary, x, y, evaluations)
+        } else {
+            pixel_color(config, &summary, &results[view.index(x, y)], x, y)
+        }
+    }, on_progress)
+}
+
+/// Renders the polynomial whose roots are `config.roots`, in row-major order.
+/// Fails with `RenderError::NoRoots` if `config.roots` has been emptied.
+pub fn render(config: &RenderConfig) -> Result<Vec<Pixel>, RenderError> {
+    Ok(render_with_progress(config, |_| {})?.0)
+}
+
+/// `render`, calling `on_progress` with the finished fraction of the work
+/// after each batch of rows; the values increase and end at exactly 1. The
+/// total number of polynomial evaluations is returned with the pixels.
+pub fn render_with_progress(config: &RenderConfig, mut on_progress: impl FnMut(f32))
+                            -> Result<(Vec<Pixel>, u64), RenderError> {
+    if config.roots.is_empty() {
+        return Err(RenderError::NoRoots);
+    }
+    let pol = Polynom::from_root_iter(config.roots.iter().cloned());
+    let der = pol.derivative();
+    let (results, classified) = classify_pixels_with_progress(&pol, &der, config, |t| on_progress(t / 2.0))?;
+    let (canvas, colored) = color_pixels_with_progress(&pol, &der, config, &results,
+                                                       |t| on_progress(0.5 + t / 2.0));
+    Ok((canvas, classified + colored))
+}
+
+/// Per-pixel `smooth_iterations` of the polynomial whose roots are
+/// `config.roots`, in row-major order, for callers that want to apply their
+/// own coloring. Fails as `render` does.
+pub fn render_field(config: &RenderConfig) -> Result<Vec<f32>, RenderError> {
+    if config.roots.is_empty() {
+        return Err(RenderError::NoRoots);
+    }
+    let pol = Polynom::from_root_iter(config.roots.iter().cloned());
+    Ok(smooth_field(config, &classify_pixels(&pol, &pol.derivative(), config)?))
+}
+
+/// `render` as tightly packed RGBA8, four bytes per pixel in row-major
+/// order with alpha 255, the layout GUI toolkits take as a texture.
+pub fn render_rgba_bytes(config: &RenderConfig) -> Result<Vec<u8>, RenderError> {
+    Ok(render(config)?.iter()
+        .flat_map(|p| {
+            let (r, g, b) = to_rgb(p);
+            [r, g, b, 0xff]
+        })
+        .collect())
+}
+
+/// Sets every pixel where `keep(x, y)` is false to `background`.
+pub fn apply_mask(canvas: &mut [Pixel], view: &Viewport, keep: impl Fn(i32, i32) -> bool,
+                  background: Pixel) {
+    for y in 0..view.height {
+        for x in 0..view.width {
+            if !keep(x, y) {
+                canvas[view.index(x, y)] = background;
+            }
+        }
+    }
+}
+
+/// A strip of square swatches, one per root in root order and at most
+/// `LEGEND_COLUMNS` to a row, showing which color marks which basin.
+pub fn render_legend(config: &RenderConfig) -> (Viewport, Vec<Pixel>) {
+    let n = config.roots.len().min(config.colors.len()) as i32;
+    let (columns, rows) = (n.clamp(1, LEGEND_COLUMNS), ((n + LEGEND_COLUMNS - 1) / LEGEND_COLUMNS).max(1));
+    let cell = SWATCH_SIZE + SWATCH_GAP;
+    let view = Viewport{width: columns * cell + SWATCH_GAP, height: rows * cell + SWATCH_GAP, scale: 1.0};
+    let mut canvas = vec![BACKGROUND; view.width as usize * view.height as usize];
+    for (i, &color) in config.colors.iter().take(n as usize).enumerate() {
+        let (column, row) = (i as i32 % LEGEND_COLUMNS, i as i32 / LEGEND_COLUMNS);
+        let (x0, y0) = (SWATCH_GAP + column * cell, SWATCH_GAP + row * cell);
+        for y in y0..y0 + SWATCH_SIZE {
+            for x in x0..x0 + SWATCH_SIZE {
+                canvas[view.index(x, y)] = color;
+            }
+        }
+    }
+    (view, canvas)
+}
+
+/// Draws the real and imaginary axes across `canvas` in `color`. With `grid`
+/// it also dots lines at multiples of the smallest power of ten that keeps
+/// them `MIN_GRID_SPACING` pixels apart at `view.scale`.
+pub fn draw_axes(canvas: &mut [Pixel], view: &Viewport, color: Pixel, grid: bool) {
+    let Some((ox, oy)) = view.complex_to_pixel(Complex::zero()) else {
+        return;
+    };
+    if grid {
+        let step = 10f32.powf((MIN_GRID_SPACING / view.scale).log10().ceil());
+        let reach = (view.width.max(view.height) as f32 / (step * view.scale)).ceil() as i32;
+        for k in (-reach..=reach).filter(|&k| k != 0) {
+            let t = k as f32 * step;
+            if let Some((x, _)) = view.complex_to_pixel(Complex::new(t, 0.0)) {
+                for y in (0..view.height).step_by(2) {
+                    canvas[view.index(x, y)] = color;
+                }
+            }
+            if let Some((_, y)) = view.complex_to_pixel(Complex::new(0.0, t)) {
+                for x in (0..view.width).step_by(2) {
+                    canvas[view.index(x, y)] = color;
+                }
+            }
+        }
+    }
+    for x in 0..view.width {
+        canvas[view.index(x, oy)] = color;
+    }
+    for y in 0..view.height {
+        canvas[view.index(ox, y)] = color;
+    }
+}
+
+/// Draws a small cross in `color` at each point that lies inside `view`.
+pub fn mark_points(canvas: &mut [Pixel], view: &Viewport, points: &[Complex<f32>], color: Pixel) {
+    for (px, py) in points.iter().filter_map(|&p| view.complex_to_pixel(p)) {
+        for d in -2..=2 {
+            for (x, y) in [(px + d, py), (px, py + d)] {
+                if (0..view.width).contains(&x) && (0..view.height).contains(&y) {
+                    canvas[view.index(x, y)] = color;
+                }
+            }
+        }
+    }
+}
+
+/// Nearest-rank percentiles (0 to 100) of the iteration counts of converged
+/// pixels, in the order given; empty if no pixel converged.
+pub fn iteration_percentiles(results: &[PixelResult], percentiles: &[f64]) -> Vec<u32> {
+    let mut iterations: Vec<u32> = results.iter().filter(|r| r.converged).map(|r| r.iterations).collect();
+    if iterations.is_empty() {
+        return Vec::new();
+    }
+    iterations.sort_unstable();
+    let n = iterations.len();
+    percentiles.iter()
+        .map(|p| {
+            let rank = (p / 100.0 * n as f64).ceil() as usize;
+            iterations[rank.clamp(1, n) - 1]
+        })
+        .collect()
+}
+
+/// Iterations per pixel as grayscale, scaled so the slowest pixel of the
+/// pass is white. Escaped and stalled pixels count like any other.
+pub fn iteration_heatmap(results: &[PixelResult]) -> Vec<u8> {
+    let max = results.iter().map(|r| r.iterations).max().unwrap_or(0).max(1);
+    results.iter()
+        .map(|r| (r.iterations as f32 * 255.0 / max as f32).round() as u8)
+        .collect()
+}
+
+pub struct RenderStats {
+    pub basin_fractions: Vec<f64>,
+    /// Pixels whose orbit escaped.
+    pub divergent_fraction: f64,
+    /// Pixels that reached no root: escaped, cycling or out of steps.
+    pub unconverged_fraction: f64,
+    pub mean_iterations: f64,
+    pub median_iterations: u32,
+    pub max_iterations: u32,
+    pub elapsed: Duration,
+}
+
+pub fn render_stats(results: &[PixelResult], roots: usize, elapsed: Duration) -> RenderStats {
+    let total = results.len() as f64;
+    let mut basins = vec![0usize; roots];
+    let (mut divergent, mut unconverged) = (0, 0);
+    for r in results {
+        if r.converged {
+            basins[r.root_index] += 1;
+        } else {
+            unconverged += 1;
+        }
+        if r.escaped {
+            divergent += 1;
+        }
+    }
+    let mut iterations: Vec<u32> = results.iter().map(|r| r.iterations).collect();
+    iterations.sort_unstable();
+    RenderStats{
+        basin_fractions: basins.iter().map(|&n| n as f64 / total).collect(),
+        divergent_fraction: divergent as f64 / total,
+        unconverged_fraction: unconverged as f64 / total,
+        mean_iterations: iterations.iter().map(|&i| i as f64).sum::<f64>() / total,
+        median_iterations: iterations[iterations.len() / 2],
+        max_iterations: *iterations.last().unwrap(),
+        elapsed,
+    }
+}
+
+/// Settings and results shared by the tests of every module.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::*;
+
+    /// The default settings on a smaller canvas that still holds every root.
+    pub fn small() -> RenderConfigBuilder {
+        let mut builder = RenderConfigBuilder::new();
+        builder.width(80).height(60).scale(10.0);
+        builder
+    }
+
+    /// A classified pixel that ended at the origin, neither cycling nor escaping.
+    pub fn synthetic(root_index: usize, iterations: u32, converged: bool) -> PixelResult {
+        PixelResult{root_index, iterations, converged, cycle: false, escaped: false, z: Complex::zero()}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::fixtures::{small, synthetic};
+
+    #[test]
+    fn empty_roots_render_as_background() {
+        let pol = Polynom::from_roots();
+        let der = pol.derivative();
+        let mut config = small().build().unwrap();
+        config.roots.clear();
+        for symmetry in [false, true] {
+            config.symmetry = symmetry;
+            let results = classify_pixels(&pol, &der, &config).unwrap();
+            assert!(color_pixels(&pol, &der, &config, &results).iter().all(|&p| p == BACKGROUND));
+        }
+    }
+
+    #[test]
+    fn uniform_seed_image_changes_nothing() {
+        let plain = render(&small().build().unwrap()).unwrap();
+        for value in [0, 128, 255] {
+            let values = vec![value; (PX_WIDTH * PX_HEIGHT) as usize];
+            let config = small().seed_image(PX_WIDTH, PX_HEIGHT, values).build().unwrap();
+            assert!(config.offsets.as_ref().unwrap().iter().all(|o| o.is_zero()));
+            assert!(render(&config).unwrap() == plain);
+        }
+    }
+
+    #[test]
+    fn evaluations_scale_with_image_size() {
+        let (_, base) = render_with_progress(&small().build().unwrap(), |_| {}).unwrap();
+        let large_config = small().width(160).height(120).scale(20.0).build().unwrap();
+        let (_, large) = render_with_progress(&large_config, |_| {}).unwrap();
+        assert!(base > 0);
+        let ratio = large as f64 / base as f64;
+        assert!((3.5..4.5).contains(&ratio), "ratio {}", ratio);
+        assert_eq!(render_with_progress(&small().build().unwrap(), |_| {}).unwrap().1, base);
+    }
+
+    #[test]
+    fn constant_polynomial_has_no_dynamics() {
+        let pol = Polynom{cs: vec![Complex::new(3.0, 0.0)]};
+        let der = pol.derivative();
+        let config = small().build().unwrap();
+        assert!(matches!(check_dynamics(&der), Err(RenderError::NoDynamics)));
+        assert!(matches!(classify_pixels(&pol, &der, &config), Err(RenderError::NoDynamics)));
+        assert!(check_dynamics(&Polynom::from_roots().derivative()).is_ok());
+        let mut emptied = config.clone();
+        emptied.roots.clear();
+        assert!(matches!(render(&emptied), Err(RenderError::NoRoots)));
+        assert!(matches!(render_rgba_bytes(&emptied), Err(RenderError::NoRoots)));
+        assert!(matches!(render_field(&emptied), Err(RenderError::NoRoots)));
+    }
+
+    #[test]
+    fn escaping_orbits_are_divergent() {
+        let pol = Polynom::from_roots();
+        let der = pol.derivative();
+        let config = small().escape_radius(5.0).divergent_color(0x123456).build().unwrap();
+        let far = classify(&pol, &der, &config, Complex::new(100.0, 0.0));
+        assert!(far.escaped && !far.converged);
+        assert_eq!(far.iterations, 1);
+        let infinite = classify(&pol, &der, &config, Complex::new(f32::INFINITY, 0.0));
+        assert!(infinite.escaped && !infinite.converged);
+        assert_eq!((infinite.root_index, infinite.iterations), (0, 0));
+        let near = classify(&pol, &der, &config, ROOTS[0]);
+        assert!(near.converged && !near.escaped);
+
+        let summary = summarize(&config, &[far, near]);
+        assert_eq!(pixel_color(&config, &summary, &far, 0, 0), 0x123456);
+        let stalled = PixelResult{escaped: false, ..far};
+        let stats = render_stats(&[far, near, stalled, near], config.roots.len(), Duration::ZERO);
+        assert_eq!((stats.divergent_fraction, stats.unconverged_fraction), (0.25, 0.5));
+    }
+
+    #[test]
+    fn sample_grid_spans_the_corner_pixels() {
+        let view = small().build().unwrap().view;
+        let grid: Vec<_> = sample_grid(&view, 7, 5).collect();
+        assert_eq!(grid.len(), 7 * 5);
+        assert_eq!(grid[0], (0, 0, view.pixel_to_complex(0.0, 0.0)));
+        assert_eq!(grid[grid.len() - 1], (6, 4, view.pixel_to_complex(79.0, 59.0)));
+        let pixels: Vec<_> = sample_grid(&view, 80, 60).map(|(_, _, z)| z).collect();
+        assert_eq!(pixels[view.index(13, 42)], view.pixel_to_complex(13.0, 42.0));
+    }
+
+    #[cfg(feature = "simd")]
+    #[test]
+    fn simd_lanes_match_scalar_over_a_row() {
+        let pol = Polynom::from_roots();
+        let der = pol.derivative();
+        let config = small().build().unwrap();
+        let y = 17;
+        let row: Vec<Complex<f32>> = (0..config.view.width).map(|x| config.start(x, y, x as f32, y as f32))
+            .collect();
+        let (mut lanes, mut evaluations) = (0, 0);
+        for chunk in row.chunks_exact(LANES) {
+            let ics: [Complex<f32>; LANES] = chunk.try_into().unwrap();
+            let (re, im) = pol.at_simd(f32x8::from_array(ics.map(|c| c.re)), f32x8::from_array(ics.map(|c| c.im)));
+            for (lane, &z) in ics.iter().enumerate() {
+                let (simd, scalar) = (Complex::new(re[lane], im[lane]), pol.at(z));
+                assert!((simd - scalar).norm() <= 1e-4 * scalar.norm().max(1.0),
+                        "at {}: {} vs {}", z, simd, scalar);
+            }
+            for (lane, r) in classify_lanes(&pol, &der, &config, ics, &mut evaluations).iter().enumerate() {
+                let scalar = classify(&pol, &der, &config, ics[lane]);
+                if let Some(r) = r {
+                    assert!(scalar.converged && r.root_index == scalar.root_index, "at {}", ics[lane]);
+                    assert_eq!(r.iterations, scalar.iterations, "at {}", ics[lane]);
+                    lanes += 1;
+                }
+            }
+        }
+        assert!(lanes * 10 >= row.len() * 9, "{} of {} lanes finished", lanes, row.len());
+        let mut counted = 0;
+        let scalar: Vec<(usize, bool)> = row.iter().map(|&z| classify(&pol, &der, &config, z))
+            .map(|r| (r.root_index, r.converged))
+            .collect();
+        let simd: Vec<(usize, bool)> = classify_row(&pol, &der, &config, y, &mut counted).iter()
+            .map(|r| (r.root_index, r.converged))
+            .collect();
+        assert_eq!(simd, scalar);
+    }
+
+    #[test]
+    fn serial_and_parallel_renders_match() {
+        let serial = small().aa(3).threads(1).build().unwrap();
+        let parallel = small().aa(3).threads(4).build().unwrap();
+        assert!(render(&serial).unwrap() == render(&parallel).unwrap());
+    }
+
+    #[test]
+    fn adaptive_aa_only_blends_edges() {
+        let config = small().build().unwrap();
+        let pol = Polynom::from_roots();
+        let results = classify_pixels(&pol, &pol.derivative(), &config).unwrap();
+        let plain = render(&config).unwrap();
+        let smoothed = render(&small().aa(3).build().unwrap()).unwrap();
+        let view = config.view;
+        let mut blended = 0;
+        for y in 0..view.height {
+            for x in 0..view.width {
+                let i = view.index(x, y);
+                if !is_edge(&view, &results, x, y) {
+                    assert_eq!(smoothed[i], plain[i], "interior pixel ({}, {})", x, y);
+                } else if smoothed[i] != plain[i] {
+                    blended += 1;
+                }
+            }
+        }
+        assert!(blended > 0);
+    }
+
+    #[test]
+    fn single_root_converges_in_one_step() {
+        let root = Complex::new(0.5, -0.25);
+        let config = small().roots(vec![root]).build().unwrap();
+        let pol = Polynom::from_root_iter([root]);
+        let results = classify_pixels(&pol, &pol.derivative(), &config).unwrap();
+        assert!(results.iter().all(|r| r.converged && r.root_index == 0 && r.iterations == 1));
+    }
+
+    #[test]
+    fn scaling_the_polynomial_keeps_the_basins() {
+        let config = small().build().unwrap();
+        let basins = |pol: &Polynom| -> Vec<(usize, bool)> {
+            classify_pixels(pol, &pol.derivative(), &config).unwrap().iter()
+                .map(|r| (r.root_index, r.converged))
+                .collect()
+        };
+        let monic = Polynom::from_roots();
+        let scale = |c: Complex<f32>| &monic * &Polynom{cs: vec![c]};
+        // A power of two scales exactly, so nothing may change.
+        assert_eq!(scale(Complex::new(4.0, 0.0)).to_monic(), monic);
+        assert!(basins(&scale(Complex::new(4.0, 0.0))) == basins(&monic));
+        // Other factors round differently, which only moves a few edge pixels.
+        let scaled = scale(Complex::new(2.5, -1.0));
+        let same = basins(&scaled).iter().zip(basins(&scaled.to_monic())).filter(|(a, b)| **a == *b).count();
+        assert!(same * 100 >= 99 * 80 * 60, "{} of {} pixels agree", same, 80 * 60);
+    }
+
+    #[test]
+    fn oversized_canvas_reports_its_bytes() {
+        let per_pixel = mem::size_of::<Pixel>() + mem::size_of::<PixelResult>();
+        let err = small().width(1000).height(1000).max_memory(1 << 20).build().err().unwrap();
+        let bytes = 1000 * 1000 * per_pixel;
+        assert!(matches!(err, RenderError::TooLarge{bytes: Some(b), limit: 1048576, ..} if b == bytes));
+        assert!(err.to_string().contains(&format!("needs {} bytes", bytes)), "{}", err);
+        let huge = Viewport{width: i32::MAX, height: i32::MAX, scale: 1.0};
+        assert!(matches!(check_memory(&huge, usize::MAX), Err(RenderError::TooLarge{bytes: None, ..})));
+        assert!(small().width(1000).height(1000).max_memory(bytes).build().is_ok());
+    }
+
+    #[test]
+    fn index_does_not_overflow_i32() {
+        let view = Viewport{width: 70_000, height: 70_000, scale: 1.0};
+        assert!(view.width as i64 * view.height as i64 > i32::MAX as i64);
+        assert_eq!(view.index(0, 40_000), 2_800_000_000);
+        assert_eq!(view.index(69_999, 69_999), 4_899_999_999);
+        assert_eq!(view.index(12, 30_678), 30_678 * 70_000 + 12);
+    }
+
+    #[test]
+    fn builder_defaults_are_the_original_settings() {
+        let config = RenderConfigBuilder::new().build().unwrap();
+        assert_eq!((config.view.width, config.view.height, config.view.scale),
+                   (PX_WIDTH, PX_HEIGHT, PIXELS_PER_UNIT as f32));
+        assert_eq!((config.roots.as_slice(), config.colors.as_slice()), (ROOTS, crate::COLORS));
+        assert_eq!((config.steps, config.epsilon, config.escape_radius),
+                   (STEPS as u32, EPSILON, ESCAPE_RADIUS));
+        assert_eq!((config.aa, config.patience, config.cycle_tolerance), (1, 0, 0.0));
+        assert!(!config.symmetry && !config.dither && config.only_root.is_none() && config.offsets.is_none());
+        assert!(matches!(config.coloring, Coloring::Basin) && matches!(config.fallback, Fallback::Nearest));
+    }
+
+    /// The image the binary wrote before it had options, with its two later
+    /// fixes applied: the roots expanded as products of `z - root` rather
+    /// than `1 - root * z`, and a root reached within `EPSILON` rather than
+    /// exactly. Pixels that reach no root take the nearest one's color.
+    fn historical_image() -> Vec<Pixel> {
+        let pol = Polynom::from_roots();
+        let der = pol.derivative();
+        let view = Viewport::default();
+        map_pixels(&view, 0, |x, y, _| {
+            let mut c = view.pixel_to_complex(x as f32, y as f32);
+            for _ in 0..STEPS {
+                let (yp, yd) = (pol.at(c), der.at(c));
+                if yd == Complex::zero() || c.is_nan() {
+                    break;
+                }
+                c -= yp / yd;
+                if let Some(i) = ROOTS.iter().position(|&r| (c - r).norm() < EPSILON) {
+                    return crate::COLORS[i];
+                }
+            }
+            let nearest = (0..ROOTS.len()).fold(0, |best, i| {
+                if (c - ROOTS[i]).norm() < (c - ROOTS[best]).norm() { i } else { best }
+            });
+            crate::COLORS[nearest]
+        }, |_| {}).0
+    }
+
+    #[test]
+    fn default_config_renders_the_historical_image() {
+        let config = RenderConfig::default();
+        assert_eq!(config.to_string(), RenderConfigBuilder::new().build().unwrap().to_string());
+        assert_eq!((config.view.width, config.view.height, config.view.scale), (800, 600, 100.0));
+        let image = render(&config).unwrap();
+        assert_eq!(image.len(), 800 * 600);
+        let historical = historical_image();
+        let first = image.iter().zip(&historical).position(|(a, b)| a != b);
+        assert_eq!(first, None, "pixel {:?} differs", first.map(|i| (i % 800, i / 800)));
+    }
+
+    #[test]
+    fn builder_rejects_invalid_settings() {
+        let err = |builder: &RenderConfigBuilder| builder.build().err().unwrap();
+        assert!(matches!(err(small().scale(0.0)), RenderError::InvalidScale(_)));
+        assert!(matches!(err(small().width(0)), RenderError::InvalidSize{width: 0, height: 60}));
+        assert!(matches!(err(small().epsilon(f32::NAN)), RenderError::InvalidEpsilon(_)));
+        assert!(matches!(err(small().roots(Vec::new())), RenderError::NoRoots));
+        assert!(matches!(err(small().colors(vec![0xff0000])),
+                         RenderError::NotEnoughColors{roots: 5, colors: 1}));
+        assert!(matches!(err(small().roots(vec![Complex::new(10.0, 0.0)])), RenderError::RootOutOfBounds(_)));
+        let mut wide = crate::COLORS.to_vec();
+        wide[2] = 0x11223344;
+        assert!(matches!(err(small().colors(wide)), RenderError::InvalidColor(0x11223344)));
+        assert!(matches!(err(small().cycle_color(0x1000000)), RenderError::InvalidColor(0x1000000)));
+        assert!(matches!(err(small().divergent_color(0xff000000)), RenderError::InvalidColor(_)));
+        assert!(matches!(err(small().two_tone(0xffffff, 0x01000000)), RenderError::InvalidColor(_)));
+        assert!(small().colors(vec![0xffffff; 5]).build().is_ok());
+        assert!(matches!(err(small().only_root(5)), RenderError::InvalidRootIndex{index: 5, roots: 5}));
+    }
+
+    #[test]
+    fn critical_point_is_marked_at_its_pixel() {
+        let cube = Polynom{cs: [-1.0, 0.0, 0.0, 1.0].iter().map(|&c| Complex::new(c, 0.0)).collect()};
+        let critical = cube.derivative().derivative().find_roots();
+        assert_eq!(critical.len(), 1);
+        assert!(critical[0].norm() < 1e-6);
+        let view = small().build().unwrap().view;
+        let mut canvas = vec![BACKGROUND; 80 * 60];
+        mark_points(&mut canvas, &view, &[critical[0], Complex::new(100.0, 0.0)], 0xffffff);
+        assert_eq!(view.complex_to_pixel(critical[0]), Some((40, 30)));
+        for (x, y) in [(40, 30), (38, 30), (42, 30), (40, 28), (40, 32)] {
+            assert_eq!(canvas[view.index(x, y)], 0xffffff, "({}, {})", x, y);
+        }
+        assert_eq!(canvas.iter().filter(|&&p| p == 0xffffff).count(), 9);
+    }
+
+    /// z³ - 2z + 2, whose Newton map sends 0 to 1 and 1 back to 0.
+    fn two_cycle() -> Polynom {
+        Polynom{cs: [2.0, -2.0, 0.0, 1.0].iter().map(|&c| Complex::new(c, 0.0)).collect()}
+    }
+
+    #[test]
+    fn attracting_cycle_is_detected() {
+        let pol = two_cycle();
+        let der = pol.derivative();
+        let config = small().roots(pol.find_roots()).cycle_tolerance(1e-3).cycle_color(0x123456)
+            .build().unwrap();
+        let r = classify(&pol, &der, &config, Complex::zero());
+        assert!(r.cycle && !r.converged);
+        assert!(r.iterations < config.steps);
+        let summary = summarize(&config, &[r]);
+        assert_eq!(pixel_color(&config, &summary, &r, 0, 0), 0x123456);
+        let off = small().roots(pol.find_roots()).build().unwrap();
+        let r = classify(&pol, &der, &off, Complex::zero());
+        assert!(!r.cycle && !r.converged && r.iterations == off.steps);
+    }
+
+    #[test]
+    fn patience_stops_a_stalled_orbit() {
+        // From 0 the orbit hops between 0 and 1, so its step never shrinks.
+        let pol = two_cycle();
+        let der = pol.derivative();
+        let config = small().roots(pol.find_roots()).patience(3).build().unwrap();
+        let r = classify(&pol, &der, &config, Complex::zero());
+        assert!(!r.converged && !r.escaped && !r.cycle);
+        assert_eq!(r.iterations, 4);
+        assert!(r.iterations < config.steps);
+        let patient = small().roots(pol.find_roots()).patience(0).build().unwrap();
+        let r = classify(&pol, &der, &patient, Complex::zero());
+        assert!(!r.converged && !r.escaped && r.iterations == patient.steps);
+        let plain = render(&small().build().unwrap()).unwrap();
+        assert!(render(&small().patience(0).build().unwrap()).unwrap() == plain);
+    }
+
+    #[test]
+    fn field_is_finite_and_grows_with_iterations() {
+        let pol = Polynom::from_roots();
+        let der = pol.derivative();
+        let config = small().build().unwrap();
+        let field = render_field(&config).unwrap();
+        assert!(field.iter().all(|v| v.is_finite() && (0.0..=1.0).contains(v)));
+        let results = classify_pixels(&pol, &der, &config).unwrap();
+        let mut by_speed: Vec<(u32, f32)> = results.iter().zip(&field)
+            .filter(|(r, _)| r.converged)
+            .map(|(r, &v)| (r.iterations, v))
+            .collect();
+        by_speed.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert!(by_speed.windows(2).all(|w| w[0].1 <= w[1].1));
+        assert!(by_speed[0].1 < by_speed[by_speed.len() - 1].1);
+    }
+
+    #[test]
+    fn symmetric_render_matches_the_full_one() {
+        for coloring in [Coloring::Basin, Coloring::Smooth] {
+            let full = render(&small().coloring(coloring).build().unwrap()).unwrap();
+            let mirrored = render(&small().coloring(coloring).symmetry(true).build().unwrap()).unwrap();
+            assert!(mirrored == full);
+        }
+        let (_, full) = render_with_progress(&small().build().unwrap(), |_| {}).unwrap();
+        let (_, mirrored) = render_with_progress(&small().symmetry(true).build().unwrap(), |_| {}).unwrap();
+        assert!(mirrored < full * 3 / 4, "{} of {} evaluations", mirrored, full);
+    }
+
+    #[test]
+    fn fallbacks_only_change_unconverged_pixels() {
+        let unity: Vec<Complex<f32>> = (0..4).map(|k| Complex::from_polar(1.0, k as f32 * TAU / 4.0))
+            .collect();
+        let pol = Polynom::from_root_iter(unity.iter().cloned());
+        let der = pol.derivative();
+        let classify_with = |fallback| {
+            let config = small().roots(unity.clone()).steps(4).fallback(fallback).build().unwrap();
+            classify_pixels(&pol, &der, &config).unwrap()
+        };
+        let (nearest, argument) = (classify_with(Fallback::Nearest), classify_with(Fallback::Argument));
+        assert!(nearest.iter().any(|r| r.converged) && nearest.iter().any(|r| !r.converged));
+        for (n, a) in nearest.iter().zip(&argument) {
+            assert_eq!((n.converged, n.iterations), (a.converged, a.iterations));
+            if n.converged {
+                assert_eq!(n.root_index, a.root_index);
+            } else if !a.escaped {
+                let angle = |i: usize| angle_between(a.z.arg(), unity[i].arg());
+                assert!((0..4).all(|i| angle(a.root_index) <= angle(i)), "final point {}", a.z);
+            }
+        }
+    }
+
+    #[test]
+    fn progress_increases_to_one() {
+        for config in [small().build().unwrap(), small().aa(2).symmetry(true).threads(3).build().unwrap()] {
+            let mut seen = Vec::new();
+            render_with_progress(&config, |t| seen.push(t)).unwrap();
+            assert!(seen.len() > 2 && seen.len() <= 2 * PROGRESS_STEPS, "{} calls", seen.len());
+            assert!(seen.windows(2).all(|w| w[0] < w[1]), "{:?}", seen);
+            assert_eq!(seen.last(), Some(&1.0));
+        }
+    }
+
+    #[test]
+    fn contains_includes_the_edges() {
+        assert!(between(1.0, 1.0, 2.0) && between(2.0, 1.0, 2.0) && !between(2.5, 1.0, 2.0));
+        assert!(!between(f32::NAN, 1.0, 2.0));
+        let view = small().build().unwrap().view;
+        assert!(view.contains(Complex::new(4.0, -3.0)) && view.contains(Complex::zero()));
+        assert!(!view.contains(Complex::new(4.01, 0.0)) && !view.contains(Complex::new(0.0, -3.01)));
+    }
+
+    #[test]
+    fn dither_only_moves_the_lowest_bit() {
+        let smooth = |dither| {
+            render(&small().coloring(Coloring::Smooth).dither(dither).build().unwrap()).unwrap()
+        };
+        let (plain, dithered) = (smooth(false), smooth(true));
+        assert!(dithered == smooth(true));
+        let mut changed = 0;
+        for (p, d) in plain.iter().zip(&dithered) {
+            let ((r0, g0, b0), (r1, g1, b1)) = (to_rgb(p), to_rgb(d));
+            assert!([(r0, r1), (g0, g1), (b0, b1)].iter().all(|&(a, b)| a.abs_diff(b) <= 1),
+                    "{:06x} vs {:06x}", p, d);
+            changed += (p != d) as usize;
+        }
+        assert!(changed > 0);
+    }
+
+    #[test]
+    fn non_finite_orbits_skip_the_fallback() {
+        let pol = Polynom::from_roots();
+        let der = pol.derivative();
+        for fallback in [Fallback::Nearest, Fallback::Argument] {
+            let config = small().fallback(fallback).build().unwrap();
+            for ic in [Complex::new(0.0, f32::NEG_INFINITY), Complex::new(f32::NAN, 1.0)] {
+                let r = classify(&pol, &der, &config, ic);
+                assert!(r.escaped && !r.converged && !r.z.is_finite());
+                assert_eq!(r.root_index, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn only_root_masks_exactly_its_basin() {
+        let pol = Polynom::from_roots();
+        let results = classify_pixels(&pol, &pol.derivative(), &small().build().unwrap()).unwrap();
+        for index in [0, 3] {
+            let mask = render(&small().only_root(index).build().unwrap()).unwrap();
+            for (r, &p) in results.iter().zip(&mask) {
+                let inside = r.converged && r.root_index == index;
+                assert_eq!(p, if inside { MASK_FOREGROUND } else { BACKGROUND });
+            }
+            assert!(mask.contains(&MASK_FOREGROUND) && mask.contains(&BACKGROUND));
+        }
+    }
+
+    #[test]
+    fn fractional_scale_keeps_the_origin_centered() {
+        let view = small().width(81).height(61).scale(12.5).build().unwrap().view;
+        assert_eq!(view.pixel_to_complex(40.0, 30.0), Complex::zero());
+        assert_eq!(view.complex_to_pixel(Complex::zero()), Some((40, 30)));
+        let corner = view.pixel_to_complex(80.0, 60.0);
+        assert!((corner - Complex::new(3.2, 2.4)).norm() < 1e-6, "{}", corner);
+        assert_eq!(view.complex_to_pixel(view.pixel_to_complex(7.0, 51.0)), Some((7, 51)));
+    }
+
+    #[test]
+    fn percentiles_of_a_known_distribution() {
+        // 100 converged pixels taking 1 to 100 iterations, plus slow ones that never converged.
+        let mut results: Vec<PixelResult> = (1..=100).map(|i| synthetic(0, i, true)).collect();
+        results.extend((0..50).map(|_| synthetic(0, 500, false)));
+        assert_eq!(iteration_percentiles(&results, &[50.0, 90.0, 99.0]), vec![50, 90, 99]);
+        assert_eq!(iteration_percentiles(&results, &[0.0, 100.0]), vec![1, 100]);
+        let skewed: Vec<PixelResult> = (0..10).map(|i| synthetic(0, if i < 9 { 3 } else { 40 }, true)).collect();
+        assert_eq!(iteration_percentiles(&skewed, &[50.0, 90.0, 99.0]), vec![3, 3, 40]);
+        let stuck: Vec<PixelResult> = (0..10).map(|i| synthetic(0, i, false)).collect();
+        assert!(iteration_percentiles(&stuck, &[50.0, 90.0, 99.0]).is_empty());
+        assert!(iteration_percentiles(&[], &[50.0]).is_empty());
+    }
+
+    #[test]
+    fn legend_has_one_swatch_per_root() {
+        for n in [5, 11] {
+            let roots: Vec<Complex<f32>> = (0..n).map(|k| Complex::from_polar(1.0, k as f32 * TAU / n as f32))
+                .collect();
+            let colors: Vec<Pixel> = (1..=n).map(|k| k * 0x010203).collect();
+            let config = small().roots(roots).colors(colors.clone()).build().unwrap();
+            let (view, legend) = render_legend(&config);
+            let rows = (n as i32 + LEGEND_COLUMNS - 1) / LEGEND_COLUMNS;
+            let cell = SWATCH_SIZE + SWATCH_GAP;
+            assert_eq!((view.width, view.height),
+                       ((n as i32).min(LEGEND_COLUMNS) * cell + SWATCH_GAP, rows * cell + SWATCH_GAP));
+            // A swatch's top-left pixel has background above and to its left.
+            let mut swatches = Vec::new();
+            for y in 0..view.height {
+                for x in 0..view.width {
+                    let p = legend[view.index(x, y)];
+                    let above = if y > 0 { legend[view.index(x, y - 1)] } else { BACKGROUND };
+                    let left = if x > 0 { legend[view.index(x - 1, y)] } else { BACKGROUND };
+                    if p != BACKGROUND && above == BACKGROUND && left == BACKGROUND {
+                        swatches.push(p);
+                    }
+                }
+            }
+            assert_eq!(swatches, colors, "{} roots", n);
+            for &color in &colors {
+                let area = legend.iter().filter(|&&p| p == color).count();
+                assert_eq!(area, (SWATCH_SIZE * SWATCH_SIZE) as usize);
+            }
+        }
+    }
+
+    #[test]
+    fn axes_cross_at_the_origin_pixel() {
+        let view = Viewport{width: 201, height: 101, scale: 25.0};
+        let (ox, oy) = view.complex_to_pixel(Complex::zero()).unwrap();
+        assert_eq!((ox, oy), (100, 50));
+        let mut canvas = vec![BACKGROUND; 201 * 101];
+        draw_axes(&mut canvas, &view, 0xff0000, false);
+        for y in 0..view.height {
+            for x in 0..view.width {
+                let on_axis = x == ox || y == oy;
+                assert_eq!(canvas[view.index(x, y)] == 0xff0000, on_axis, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn grid_step_follows_the_scale() {
+        // Power-of-ten steps of 0.1, 1 and 10 units, all 25 pixels apart.
+        for scale in [250.0, 25.0, 2.5] {
+            let view = Viewport{width: 201, height: 101, scale};
+            let mut canvas = vec![BACKGROUND; 201 * 101];
+            draw_axes(&mut canvas, &view, 0xff0000, true);
+            // Rows 10 and 40 are dotted by vertical gridlines only.
+            let dotted = |x: i32| [10, 40].iter().all(|&y| canvas[view.index(x, y)] != BACKGROUND);
+            for x in [0, 25, 50, 75, 125, 150, 175, 200] {
+                assert!(dotted(x), "x = {} at scale {}", x, scale);
+            }
+            for x in [12, 30, 60, 110] {
+                assert!(!dotted(x), "x = {} at scale {}", x, scale);
+            }
+        }
+        // At 10 px/unit 1-unit lines would be too dense, so they are 10 units apart.
+        let view = Viewport{width: 201, height: 101, scale: 10.0};
+        let mut canvas = vec![BACKGROUND; 201 * 101];
+        draw_axes(&mut canvas, &view, 0xff0000, true);
+        let dotted: Vec<i32> = (0..view.width).filter(|&x| canvas[view.index(x, 10)] != BACKGROUND).collect();
+        assert_eq!(dotted, vec![0, 100, 200]);
+    }
+
+    #[test]
+    fn heatmap_is_brightest_where_iterations_are_most() {
+        let results: Vec<PixelResult> = [0, 5, 10, 20, 20].iter().map(|&i| synthetic(0, i, i < 20)).collect();
+        assert_eq!(iteration_heatmap(&results), vec![0, 64, 128, 255, 255]);
+        assert_eq!(iteration_heatmap(&[synthetic(0, 0, false); 3]), vec![0; 3]);
+
+        let pol = Polynom::from_roots();
+        let results = classify_pixels(&pol, &pol.derivative(), &small().build().unwrap()).unwrap();
+        let heatmap = iteration_heatmap(&results);
+        let slowest = results.iter().map(|r| r.iterations).max().unwrap();
+        for (r, &v) in results.iter().zip(&heatmap) {
+            assert_eq!(v == 255, r.iterations == slowest);
+        }
+        let mut by_iterations: Vec<(u32, u8)> = results.iter().map(|r| r.iterations).zip(heatmap).collect();
+        by_iterations.sort_unstable();
+        assert!(by_iterations.windows(2).all(|w| w[0].1 <= w[1].1));
+    }
+
+    #[test]
+    fn rgba_bytes_follow_the_rendered_pixels() {
+        let config = small().build().unwrap();
+        let (width, height) = (config.view.width as usize, config.view.height as usize);
+        let (pixels, bytes) = (render(&config).unwrap(), render_rgba_bytes(&config).unwrap());
+        assert_eq!(bytes.len(), width * height * 4);
+        for (x, y) in [(0, 0), (79, 0), (13, 42), (40, 30), (79, 59)] {
+            let i = (y * width + x) * 4;
+            let (r, g, b) = to_rgb(&pixels[y * width + x]);
+            assert_eq!(bytes[i..i + 4], [r, g, b, 0xff], "({}, {})", x, y);
+        }
+        assert!(bytes.chunks_exact(4).zip(&pixels).all(|(rgba, p)| from_rgb(rgba[0], rgba[1], rgba[2]) == *p));
+        assert!(bytes.chunks_exact(4).all(|rgba| rgba[3] == 0xff));
+    }
+
+    #[test]
+    fn mask_clears_only_pixels_outside_the_circle() {
+        let config = small().build().unwrap();
+        let view = config.view;
+        let original = render(&config).unwrap();
+        let mut canvas = original.clone();
+        let inside = |x: i32, y: i32| ((x - 40) as f32).hypot((y - 30) as f32) <= 20.0;
+        apply_mask(&mut canvas, &view, inside, 0x000000);
+        for y in 0..view.height {
+            for x in 0..view.width {
+                let i = view.index(x, y);
+                assert_eq!(canvas[i], if inside(x, y) { original[i] } else { 0x000000 }, "({}, {})", x, y);
+            }
+        }
+    }
+}